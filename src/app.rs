@@ -0,0 +1,354 @@
+use crate::config::{merge_mirrors, Config};
+use crate::error::*;
+use crate::{bun, cargo, curl, git, npm, pip, pnpm, yarn};
+use clap::{clap_derive::ArgEnum, ValueEnum};
+use serde::Deserialize;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use strum::EnumMessage;
+use strum_macros::AsRefStr;
+
+#[derive(AsRefStr, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Capability {
+    Proxy,
+    /// Hosts accessed without proxy
+    NoProxy,
+    /// Proxy only for urls matching a pattern
+    #[strum(serialize = "url proxy")]
+    UrlProxy,
+    Mirror,
+    /// Mirror only for packages of a scope, like `@scope:registry` of npm
+    #[strum(serialize = "scoped mirror")]
+    ScopedMirror,
+    /// Mirror searched besides the main one, like `extra-index-url` of pip
+    #[strum(serialize = "extra mirror")]
+    ExtraMirror,
+}
+
+/// Where a setting lives, ordered by precedence: settings of a former scope override latter ones
+#[derive(AsRefStr, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+#[strum(serialize_all = "lowercase")]
+pub enum Scope {
+    /// Config of the project in current directory
+    Project,
+    /// Config of current user
+    User,
+    /// Config shared by all users of the machine
+    System,
+}
+
+/// A proxy found in the config of an app
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Proxy {
+    pub scope: Scope,
+    /// Config file containing the proxy, `None` when it's unknown
+    pub path: Option<PathBuf>,
+    /// Config key of the proxy, like "http.proxy"
+    pub key: String,
+    pub subsection: Option<String>,
+    pub proxy: String,
+}
+
+impl Proxy {
+    pub fn new(scope: Scope, path: Option<&Path>, key: &str, proxy: impl Into<String>) -> Self {
+        Proxy {
+            scope,
+            path: path.map(Path::to_path_buf),
+            key: key.to_string(),
+            subsection: None,
+            proxy: proxy.into(),
+        }
+    }
+}
+
+impl Display for Proxy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.subsection {
+            Some(sub) => write!(f, "\"{}\": {}", sub, &self.proxy),
+            None => write!(f, "{}", &self.proxy),
+        }
+    }
+}
+
+/// A mirror found in the config of an app
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mirror {
+    pub scope: Scope,
+    /// Config file containing the mirror, `None` when it's unknown
+    pub path: Option<PathBuf>,
+    /// Config key selecting the mirror, like "registry"
+    pub key: String,
+    /// Name of the mirror as accepted by `App::find_mirror()`, `None` for an unknown mirror
+    pub name: Option<String>,
+    pub url: String,
+    /// Package scope using the mirror, like "@corp", `None` for all packages
+    pub subsection: Option<String>,
+    /// Credential of the mirror, masked by `mask_secret()`
+    pub auth: Option<String>,
+}
+
+impl Mirror {
+    pub fn new(
+        scope: Scope,
+        path: Option<&Path>,
+        key: &str,
+        name: Option<String>,
+        url: &str,
+    ) -> Self {
+        Mirror {
+            scope,
+            path: path.map(Path::to_path_buf),
+            key: key.to_string(),
+            name,
+            url: url.to_string(),
+            subsection: None,
+            auth: None,
+        }
+    }
+}
+
+impl Display for Mirror {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(sub) = &self.subsection {
+            write!(f, "\"{}\": ", sub)?;
+        }
+        match &self.name {
+            Some(name) => write!(f, "{}", name)?,
+            None => write!(f, "{}", &self.url)?,
+        }
+        match &self.auth {
+            Some(auth) => write!(f, " (auth: {})", auth),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A mirror which could be set to an app, built-in or defined in lane config
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct MirrorEntry {
+    /// Name of the mirror, filled from the key of lane config
+    #[serde(skip)]
+    pub name: String,
+    pub url: String,
+    /// Credential of the registry, like an auth token
+    #[serde(default)]
+    pub auth: Option<String>,
+}
+
+/// A program whose proxies and/or mirrors can be managed by lane.
+///
+/// Every method has a default implementation returning `LaneError::Unsupported`, so a backend only
+/// implements what its `capabilities()` declares.
+pub trait App: Sync {
+    fn name(&self) -> &'static str;
+
+    fn capabilities(&self) -> &'static [Capability];
+
+    /// Scopes accepted by all methods below
+    fn scopes(&self) -> &'static [Scope] {
+        &[Scope::User]
+    }
+
+    /// Config files which could be modified in the scope, to record their changes for undo
+    fn config_files(&self, _scope: Scope) -> Vec<PathBuf> {
+        vec![]
+    }
+
+    fn get_proxies(&self, _scope: Scope) -> Result<Vec<Proxy>, LaneError> {
+        Err(make_unsupported_error(self.name(), Capability::Proxy))
+    }
+
+    fn set_proxy(&self, _scope: Scope, _proxy: &str) -> Result<(), LaneError> {
+        Err(make_unsupported_error(self.name(), Capability::Proxy))
+    }
+
+    fn unset_proxy(&self, _scope: Scope) -> Result<(), LaneError> {
+        Err(make_unsupported_error(self.name(), Capability::Proxy))
+    }
+
+    fn set_url_proxy(&self, _scope: Scope, _url: &str, _proxy: &str) -> Result<(), LaneError> {
+        Err(make_unsupported_error(self.name(), Capability::UrlProxy))
+    }
+
+    /// Unset proxy of the url pattern, or proxies of all url patterns when `url` is `None`
+    fn unset_url_proxy(&self, _scope: Scope, _url: Option<&str>) -> Result<(), LaneError> {
+        Err(make_unsupported_error(self.name(), Capability::UrlProxy))
+    }
+
+    fn set_no_proxy(&self, _scope: Scope, _hosts: &[String]) -> Result<(), LaneError> {
+        Err(make_unsupported_error(self.name(), Capability::NoProxy))
+    }
+
+    fn unset_no_proxy(&self, _scope: Scope) -> Result<(), LaneError> {
+        Err(make_unsupported_error(self.name(), Capability::NoProxy))
+    }
+
+    /// Mirrors compiled into lane
+    fn builtin_mirrors(&self) -> Vec<MirrorEntry> {
+        vec![]
+    }
+
+    /// Mirrors defined in lane config, followed by built-in ones not overridden by them
+    fn mirrors(&self) -> Result<Vec<MirrorEntry>, LaneError> {
+        Ok(merge_mirrors(Config::load()?.mirrors_of(self.name()), self.builtin_mirrors()))
+    }
+
+    fn find_mirror(&self, name: &str) -> Result<MirrorEntry, LaneError> {
+        self.mirrors()?
+            .into_iter()
+            .find(|m| m.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| make_unknown_mirror_error(name))
+    }
+
+    fn find_mirror_by_url(&self, url: &str) -> Result<Option<MirrorEntry>, LaneError> {
+        Ok(find_mirror_by_url(self.mirrors()?, url))
+    }
+
+    /// Find mirror by name, or by url when a url is given. An unknown url is used as is, and
+    /// named after its host.
+    fn resolve_mirror(&self, mirror: &str) -> Result<MirrorEntry, LaneError> {
+        if !mirror.contains("://") {
+            return self.find_mirror(mirror);
+        }
+        let url = validate_mirror_url(mirror)?;
+        match self.find_mirror_by_url(mirror)? {
+            Some(known) => Ok(known),
+            None => Ok(MirrorEntry {
+                name: url.host_str().unwrap_or(self.name()).to_string(),
+                url: mirror.to_string(),
+                auth: None,
+            }),
+        }
+    }
+
+    fn get_mirror(&self, _scope: Scope) -> Result<Option<Mirror>, LaneError> {
+        Err(make_unsupported_error(self.name(), Capability::Mirror))
+    }
+
+    fn set_mirror(&self, _scope: Scope, _mirror: &MirrorEntry) -> Result<(), LaneError> {
+        Err(make_unsupported_error(self.name(), Capability::Mirror))
+    }
+
+    fn unset_mirror(&self, _scope: Scope) -> Result<(), LaneError> {
+        Err(make_unsupported_error(self.name(), Capability::Mirror))
+    }
+
+    fn get_scoped_mirrors(&self, _scope: Scope) -> Result<Vec<Mirror>, LaneError> {
+        Err(make_unsupported_error(self.name(), Capability::ScopedMirror))
+    }
+
+    fn set_scoped_mirror(
+        &self,
+        _scope: Scope,
+        _package_scope: &str,
+        _mirror: &MirrorEntry,
+    ) -> Result<(), LaneError> {
+        Err(make_unsupported_error(self.name(), Capability::ScopedMirror))
+    }
+
+    /// Unset mirror of the package scope, or mirrors of all package scopes when it's `None`
+    fn unset_scoped_mirror(
+        &self,
+        _scope: Scope,
+        _package_scope: Option<&str>,
+    ) -> Result<(), LaneError> {
+        Err(make_unsupported_error(self.name(), Capability::ScopedMirror))
+    }
+
+    fn get_extra_mirrors(&self, _scope: Scope) -> Result<Vec<Mirror>, LaneError> {
+        Err(make_unsupported_error(self.name(), Capability::ExtraMirror))
+    }
+
+    fn add_extra_mirror(&self, _scope: Scope, _mirror: &MirrorEntry) -> Result<(), LaneError> {
+        Err(make_unsupported_error(self.name(), Capability::ExtraMirror))
+    }
+
+    /// Remove the extra mirror, or all extra mirrors when it's `None`
+    fn unset_extra_mirror(
+        &self,
+        _scope: Scope,
+        _mirror: Option<&MirrorEntry>,
+    ) -> Result<(), LaneError> {
+        Err(make_unsupported_error(self.name(), Capability::ExtraMirror))
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        self.capabilities().contains(&capability)
+    }
+
+    fn supports_scope(&self, scope: Scope) -> bool {
+        self.scopes().contains(&scope)
+    }
+}
+
+/// Mirror of a variant of built-in mirror enum, whose url is the strum message
+pub fn mirror_entry<M: ValueEnum + EnumMessage>(mirror: &M) -> MirrorEntry {
+    MirrorEntry {
+        name: mirror.to_possible_value().map(|v| v.get_name()).unwrap_or_default().to_string(),
+        url: mirror.get_message().unwrap_or_default().to_string(),
+        auth: None,
+    }
+}
+
+/// Mirrors of all variants of a built-in mirror enum
+pub fn mirror_entries<M: ValueEnum + EnumMessage>() -> Vec<MirrorEntry> {
+    M::value_variants().iter().map(mirror_entry).collect()
+}
+
+/// Whether two registry urls are the same, ignoring the difference of scheme and trailing slash.
+/// Protocol prefixes like "sparse+" are not ignored.
+pub fn same_registry(url1: &str, url2: &str) -> bool {
+    let split = |url: &str| {
+        let url = url.trim_end_matches('/');
+        match url.split_once("://") {
+            Some((scheme, rest)) => {
+                (scheme.split_once('+').map(|(p, _)| p.to_string()), rest.to_string())
+            }
+            None => (None, url.to_string()),
+        }
+    };
+    split(url1) == split(url2)
+}
+
+/// Mask a secret like an auth token for output, only the last 4 characters of a long one are kept.
+/// References to env vars like "${NPM_TOKEN}" are not secrets and kept as is.
+pub fn mask_secret(secret: &str) -> String {
+    if secret.starts_with("${") && secret.ends_with('}') {
+        return secret.to_string();
+    }
+    let chars: Vec<char> = secret.chars().collect();
+    let visible = if chars.len() >= 12 { 4 } else { 0 };
+    "*".repeat(8) + &chars[chars.len() - visible..].iter().collect::<String>()
+}
+
+pub fn find_mirror_by_url(mirrors: Vec<MirrorEntry>, url: &str) -> Option<MirrorEntry> {
+    mirrors.into_iter().find(|m| same_registry(&m.url, url))
+}
+
+// Adding a new app only needs a new module and an entry here
+static APPS: &[&dyn App] = &[
+    &curl::Curl,
+    &git::Git,
+    &cargo::Cargo,
+    &npm::Npm,
+    &yarn::Yarn,
+    &pnpm::Pnpm,
+    &bun::Bun,
+    &pip::Pip,
+];
+
+pub fn apps() -> &'static [&'static dyn App] {
+    APPS
+}
+
+pub fn apps_with(capability: Capability) -> impl Iterator<Item = &'static dyn App> {
+    apps().iter().copied().filter(move |app| app.supports(capability))
+}
+
+pub fn app_names_with(capability: Capability) -> Vec<&'static str> {
+    apps_with(capability).map(|app| app.name()).collect()
+}
+
+pub fn find_app(name: &str) -> Option<&'static dyn App> {
+    apps().iter().copied().find(|app| app.name() == name)
+}
@@ -1,70 +1,204 @@
-use clap::{clap_derive::ArgEnum, Args, Parser, Subcommand};
-use strum_macros::AsRefStr;
+use crate::output::Format;
+use clap::{builder::PossibleValuesParser, Args, Parser, Subcommand};
+use lane::{app_names_with, Capability, Scope};
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about, long_about = None)]
+#[clap(after_help = "EXIT CODES:
+    0    All apps succeeded
+    1    All apps failed
+    2    Invalid command line
+    3    Some apps failed
+    4    Nothing to do")]
 // #[clap(propagate_version = true)]
 pub struct Cli {
     #[clap(subcommand)]
     pub command: Commands,
+    /// Scope of the config, get-* commands query all scopes and set/clear-* commands modify user
+    /// scope by default
+    #[clap(long, value_enum, global = true)]
+    pub scope: Option<Scope>,
+    /// Output format
+    #[clap(long, value_enum, global = true, default_value = "text")]
+    pub format: Format,
 }
 
 #[derive(Debug, Subcommand)]
 pub enum Commands {
     GetProxy(ProxyableAppArgs),
-    ClearProxy(ProxyableAppArgs),
+    ClearProxy(ClearProxyArgs),
     SetProxy(SetProxyArgs),
     GetMirror(MirrorableAppArgs),
-    ClearMirror(MirrorableAppArgs),
-    SetMirror {
-        #[clap(subcommand)]
-        app: MirrorableAppsWithParam,
-    },
+    ClearMirror(ClearMirrorArgs),
+    SetMirror(SetMirrorArgs),
+    /// Apply a profile of lane config to all apps
+    Use(UseArgs),
+    /// Manage profiles in lane config
+    Profile(ProfileArgs),
+    /// Revert the latest change made by lane
+    Undo(UndoArgs),
+    /// Show changes made by lane, which could be reverted by undo
+    History,
 }
 
 #[derive(Debug, Args)]
 pub struct ProxyableAppArgs {
     /// Supported apps
-    #[clap(value_enum)]
-    pub app: Option<ProxyableApps>,
+    #[clap(value_parser = PossibleValuesParser::new(app_names_with(Capability::Proxy)))]
+    pub app: Option<String>,
 }
 
 #[derive(Debug, Args)]
 pub struct MirrorableAppArgs {
     /// Supported apps
-    #[clap(value_enum)]
-    pub app: Option<MirrorableApps>,
+    #[clap(value_parser = PossibleValuesParser::new(app_names_with(Capability::Mirror)))]
+    pub app: Option<String>,
+    /// Show mirrors of package scopes, like `@scope:registry` of npm
+    #[clap(long)]
+    pub package_scopes: bool,
+    /// Show mirrors searched besides the main one, like `extra-index-url` of pip
+    #[clap(long, conflicts_with = "package-scopes")]
+    pub extra_mirrors: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct DryRunArgs {
+    /// Show changes to config files, without making them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ClearProxyArgs {
+    /// Supported apps
+    #[clap(value_parser = PossibleValuesParser::new(app_names_with(Capability::Proxy)))]
+    pub app: Option<String>,
+    /// Only clear proxy of urls matching the pattern, like `http.<url>.proxy` of git
+    #[clap(long, value_parser)]
+    pub url: Option<String>,
+    /// Clear proxies of all url patterns
+    #[clap(long, conflicts_with = "url")]
+    pub all_urls: bool,
+    #[clap(flatten)]
+    pub dry_run: DryRunArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ClearMirrorArgs {
+    /// Supported apps
+    #[clap(value_parser = PossibleValuesParser::new(app_names_with(Capability::Mirror)))]
+    pub app: Option<String>,
+    /// Only clear mirror of the package scope, like `@scope:registry` of npm
+    #[clap(long, value_parser)]
+    pub package_scope: Option<String>,
+    /// Clear mirrors of all package scopes
+    #[clap(long, conflicts_with = "package-scope")]
+    pub all_package_scopes: bool,
+    /// Only remove the mirror from extra mirrors, like `extra-index-url` of pip
+    #[clap(long, value_parser, conflicts_with_all = &["package-scope", "all-package-scopes"])]
+    pub extra_mirror: Option<String>,
+    /// Remove all extra mirrors
+    #[clap(long, conflicts_with_all = &["package-scope", "all-package-scopes", "extra-mirror"])]
+    pub all_extra_mirrors: bool,
+    #[clap(flatten)]
+    pub dry_run: DryRunArgs,
 }
 
 #[derive(Debug, Args)]
 pub struct SetProxyArgs {
-    #[clap(value_enum)]
-    pub app: Option<ProxyableApps>,
+    #[clap(value_parser = PossibleValuesParser::new(app_names_with(Capability::Proxy)))]
+    pub app: Option<String>,
     #[clap(short, long, value_parser)]
     pub proxy: String,
+    /// Only use the proxy for urls matching the pattern, like `http.<url>.proxy` of git
+    #[clap(long, value_parser)]
+    pub url: Option<String>,
+    /// Comma separated hosts accessed without proxy, set to apps supporting it, like `noproxy` of
+    /// npm
+    #[clap(long, value_parser, value_delimiter = ',', conflicts_with = "url")]
+    pub no_proxy: Vec<String>,
+    #[clap(flatten)]
+    pub dry_run: DryRunArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct SetMirrorArgs {
+    /// Supported apps
+    #[clap(value_parser = PossibleValuesParser::new(app_names_with(Capability::Mirror)))]
+    pub app: String,
+    /// Name of the mirror, see the app's mirror list, or url of any mirror
+    #[clap(value_parser)]
+    pub mirror: String,
+    /// Only use the mirror for packages of the scope, like `@scope:registry` of npm
+    #[clap(long, value_parser)]
+    pub package_scope: Option<String>,
+    /// Add the mirror searched besides the main one, like `extra-index-url` of pip
+    #[clap(long, conflicts_with = "package-scope")]
+    pub extra: bool,
+    /// Env var holding the auth token of the mirror, overriding the one in lane config. Without
+    /// it, tokens only come from mirrors defined in lane config.
+    #[clap(long, value_parser, value_name = "VAR")]
+    pub auth_token_env: Option<String>,
+    #[clap(flatten)]
+    pub dry_run: DryRunArgs,
 }
 
-#[derive(AsRefStr, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
-pub enum ProxyableApps {
-    Cargo,
-    Curl,
-    Git,
+#[derive(Debug, Args)]
+pub struct UseArgs {
+    /// Name of the profile
+    #[clap(value_parser)]
+    pub profile: String,
+    #[clap(flatten)]
+    pub dry_run: DryRunArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct UndoArgs {
+    /// Revert even if config files were modified after the change
+    #[clap(long)]
+    pub force: bool,
 }
 
-#[derive(AsRefStr, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
-pub enum MirrorableApps {
-    Cargo,
-    Npm,
+#[derive(Debug, Args)]
+pub struct ProfileArgs {
+    #[clap(subcommand)]
+    pub command: ProfileCommands,
 }
 
 #[derive(Debug, Subcommand)]
-pub enum MirrorableAppsWithParam {
-    Cargo {
-        #[clap(value_enum)]
-        mirror: crate::cargo::Mirrors,
-    },
-    Npm {
-        #[clap(value_enum)]
-        mirror: crate::npm::Mirrors,
-    },
+pub enum ProfileCommands {
+    List,
+    Show(ProfileNameArgs),
+    Add(AddProfileArgs),
+    Remove(ProfileNameArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ProfileNameArgs {
+    #[clap(value_parser)]
+    pub name: String,
+}
+
+#[derive(Debug, Args)]
+pub struct AddProfileArgs {
+    #[clap(value_parser)]
+    pub name: String,
+    /// Proxy of all apps, proxies are cleared when using a profile without it
+    #[clap(short, long, value_parser)]
+    pub proxy: Option<String>,
+    /// Comma separated hosts accessed without proxy
+    #[clap(long, value_parser, value_delimiter = ',')]
+    pub no_proxy: Vec<String>,
+    /// Mirror of an app as <APP>=<MIRROR>, could be repeated
+    #[clap(short, long, value_parser = parse_app_mirror)]
+    pub mirror: Vec<(String, String)>,
+}
+
+fn parse_app_mirror(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((app, mirror)) if !app.is_empty() && !mirror.is_empty() => {
+            Ok((app.to_string(), mirror.to_string()))
+        }
+        _ => Err(format!("expected <APP>=<MIRROR>, got {}", value)),
+    }
 }
@@ -0,0 +1,173 @@
+// bun: https://bun.sh/docs/runtime/bunfig#install-registry
+//
+// bun has no proxy setting in bunfig.toml, it uses HTTP_PROXY and HTTPS_PROXY of env only.
+
+use dirs::home_dir;
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+use toml_edit::{Document, InlineTable, Item, Value};
+
+use crate::{
+    app::*,
+    error::*,
+    npm::{self, get_project_dir},
+    utils::*,
+};
+
+const BUN_CONFIG: &str = "bunfig.toml";
+const BUN_GLOBAL_CONFIG: &str = ".bunfig.toml";
+const SECTION_INSTALL: &str = "install";
+const KEY_REGISTRY: &str = "registry";
+const KEY_MIRROR: &str = "install.registry";
+
+// global config is in $XDG_CONFIG_HOME if set, or home dir
+fn get_config_file_path(scope: Scope) -> Result<PathBuf, LaneError> {
+    match scope {
+        Scope::Project => Ok(get_project_dir()?.join(BUN_CONFIG)),
+        _ => match env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+            Some(dir) => Ok(PathBuf::from(dir).join(BUN_GLOBAL_CONFIG)),
+            None => Ok(home_dir().ok_or(LaneError::NoHomeDir())?.join(BUN_GLOBAL_CONFIG)),
+        },
+    }
+}
+
+fn read_config(path: &Path) -> Result<Document, LaneError> {
+    match path.exists() {
+        true => read_document(path),
+        false => Ok(Document::new()),
+    }
+}
+
+// registry is a url, or a table with url and token
+fn get_registry_from_document(document: &Document) -> Option<(&str, Option<&str>)> {
+    let registry = document.get(SECTION_INSTALL)?.get(KEY_REGISTRY)?;
+    if let Some(url) = registry.as_str() {
+        return Some((url, None));
+    }
+    let url = registry.get("url")?.as_str()?;
+    Some((url, registry.get("token").and_then(|token| token.as_str())))
+}
+
+fn set_mirror_to_document(document: &mut Document, mirror: &MirrorEntry) -> Result<(), LaneError> {
+    let install_table = get_or_insert_table(document.as_table_mut(), SECTION_INSTALL)?;
+    match &mirror.auth {
+        Some(token) => {
+            let mut registry = InlineTable::new();
+            registry.insert("url", Value::from(mirror.url.as_str()));
+            registry.insert("token", Value::from(token.as_str()));
+            install_table.insert(KEY_REGISTRY, Item::Value(Value::InlineTable(registry)));
+        }
+        None => set_string(install_table, KEY_REGISTRY, &mirror.url),
+    }
+    Ok(())
+}
+
+fn unset_mirror_to_document(document: &mut Document) -> Result<(), LaneError> {
+    let install_table = match document.get_mut(SECTION_INSTALL) {
+        Some(v) => {
+            v.as_table_like_mut().ok_or_else(|| make_failure_error("Invalid install section."))?
+        }
+        None => return Err(LaneError::NothingToDo()),
+    };
+    if install_table.remove(KEY_REGISTRY).is_none() {
+        return Err(LaneError::NothingToDo());
+    }
+    // remove install section if nothing left
+    if install_table.is_empty() {
+        document.remove(SECTION_INSTALL);
+    }
+    Ok(())
+}
+
+pub struct Bun;
+
+impl App for Bun {
+    fn name(&self) -> &'static str {
+        "bun"
+    }
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::Mirror]
+    }
+
+    fn scopes(&self) -> &'static [Scope] {
+        &[Scope::Project, Scope::User]
+    }
+
+    fn config_files(&self, scope: Scope) -> Vec<PathBuf> {
+        get_config_file_path(scope).into_iter().collect()
+    }
+
+    // bun uses the same registries as npm
+    fn builtin_mirrors(&self) -> Vec<MirrorEntry> {
+        mirror_entries::<npm::Mirrors>()
+    }
+
+    fn get_mirror(&self, scope: Scope) -> Result<Option<Mirror>, LaneError> {
+        let config_file = get_config_file_path(scope)?;
+        let document = read_config(&config_file)?;
+        let Some((url, token)) = get_registry_from_document(&document) else {
+            return Ok(None);
+        };
+        let name = self.find_mirror_by_url(url)?.map(|mirror| mirror.name);
+        let mut mirror = Mirror::new(scope, Some(&config_file), KEY_MIRROR, name, url);
+        mirror.auth = token.map(mask_secret);
+        Ok(Some(mirror))
+    }
+
+    fn set_mirror(&self, scope: Scope, mirror: &MirrorEntry) -> Result<(), LaneError> {
+        let config_file = get_config_file_path(scope)?;
+        let mut document = read_config(&config_file)?;
+        set_mirror_to_document(&mut document, mirror)?;
+        write_document(&config_file, &document)
+    }
+
+    fn unset_mirror(&self, scope: Scope) -> Result<(), LaneError> {
+        let config_file = get_config_file_path(scope)?;
+        let mut document = read_config(&config_file)?;
+        unset_mirror_to_document(&mut document)?;
+        write_document(&config_file, &document)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUNFIG: &str = "# bun\ntelemetry = false\n\n[install]\nexact = true\n";
+
+    fn edit(content: &str, f: impl Fn(&mut Document) -> Result<(), LaneError>) -> String {
+        let mut document: Document = content.parse().unwrap();
+        f(&mut document).unwrap();
+        document.to_string()
+    }
+
+    fn mirror(auth: Option<&str>) -> MirrorEntry {
+        MirrorEntry {
+            name: "corp".to_string(),
+            url: "https://npm.corp/".to_string(),
+            auth: auth.map(String::from),
+        }
+    }
+
+    #[test]
+    fn set_and_unset_mirror() {
+        let content = edit(BUNFIG, |d| set_mirror_to_document(d, &mirror(None)));
+        assert_eq!(content, BUNFIG.to_string() + "registry = \"https://npm.corp/\"\n");
+        assert_eq!(edit(&content, unset_mirror_to_document), BUNFIG);
+        assert!(unset_mirror_to_document(&mut BUNFIG.parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn set_mirror_with_token() {
+        let content = edit("", |d| set_mirror_to_document(d, &mirror(Some("secret"))));
+        let document: Document = content.parse().unwrap();
+        assert_eq!(
+            get_registry_from_document(&document),
+            Some(("https://npm.corp/", Some("secret")))
+        );
+        assert_eq!(edit(&content, unset_mirror_to_document), "");
+    }
+}
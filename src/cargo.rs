@@ -1,16 +1,16 @@
+use crate::app::*;
 use crate::error::*;
-use clap::{clap_derive::ArgEnum, ValueEnum};
+use crate::utils::*;
+use clap::clap_derive::ArgEnum;
 use dirs::home_dir;
+use log::warn;
 use std::{
-    fs::{read_to_string, write},
+    env,
     path::{Path, PathBuf},
 };
 use strum::EnumMessage;
 use strum_macros::AsRefStr;
-use toml::{
-    value::{Map, Table},
-    Value,
-};
+use toml_edit::Document;
 use trace::trace;
 
 trace::init_depth_var!();
@@ -21,189 +21,391 @@ pub enum Mirrors {
     Tuna,
     #[strum(message = "http://mirrors.ustc.edu.cn/crates.io-index")]
     Ustc,
+    #[strum(message = "https://rsproxy.cn/crates.io-index")]
+    Rsproxy,
+    #[strum(message = "sparse+https://mirrors.tuna.tsinghua.edu.cn/crates.io-index/")]
+    TunaSparse,
+    #[strum(message = "sparse+https://mirrors.ustc.edu.cn/crates.io-index/")]
+    UstcSparse,
+    #[strum(message = "sparse+https://rsproxy.cn/index/")]
+    RsproxySparse,
+}
+
+const CARGO_HOME: &str = ".cargo";
+#[cfg(not(target_os = "windows"))]
+const SYSTEM_ROOT: &str = "/";
+#[cfg(target_os = "windows")]
+const SYSTEM_ROOT: &str = "C:\\";
+const CARGO_CONFIG: &str = "config.toml";
+const CARGO_CONFIG_LEGACY: &str = "config";
+const KEY_PROXY: &str = "http.proxy";
+const KEY_MIRROR: &str = "source.crates-io.replace-with";
+
+fn get_proxy_from_document(document: &Document) -> Option<&str> {
+    document.get("http").and_then(|v| v.get("proxy")).and_then(|v| v.as_str())
 }
 
-const CARGO_CONFIG: &str = ".cargo/config";
+fn get_replace_with_from_document(document: &Document) -> Option<&str> {
+    document
+        .get("source")
+        .and_then(|v| v.get("crates-io"))
+        .and_then(|v| v.get("replace-with"))
+        .and_then(|v| v.as_str())
+}
 
 fn read_proxy_from_config(path: &Path) -> Result<Option<String>, LaneError> {
-    let content = read_to_string(path).map_err(|_| make_invalid_file_error(path))?;
-    let value: Value = content.parse().map_err(|_| make_invalid_file_error(path))?;
-    let proxy_value = match value.get("http") {
-        None => return Ok(None),
-        Some(value) => value.get("proxy"),
-    };
-    match proxy_value {
-        None => Ok(None),
-        Some(value) => Ok(value.as_str().map(|v| v.to_string())),
-    }
+    let document = read_document(path)?;
+    Ok(get_proxy_from_document(&document).map(String::from))
 }
 
-fn set_proxy_to_value(value: &mut Value, proxy: &str) -> Result<(), LaneError> {
-    // clone existed http section or create a new one
-    let mut http_value = match value.get("http") {
-        Some(v) => v.clone(),
-        None => Value::Table(Map::new()), // create if not exists
-    };
-    // insert proxy into http section
-    http_value
-        .as_table_mut()
-        .ok_or_else(|| make_failure_error("Failed to get http table."))?
-        .insert("proxy".into(), Value::String(proxy.to_string()));
-    // insert http section to orginal value
-    value
-        .as_table_mut()
-        .ok_or_else(|| make_failure_error("Failed to insert http section."))?
-        .insert("http".into(), http_value);
+fn set_proxy_to_document(document: &mut Document, proxy: &str) -> Result<(), LaneError> {
+    // get existed http section or create a new one, then insert proxy into it
+    let http_table = get_or_insert_table(document.as_table_mut(), "http")?;
+    set_string(http_table, "proxy", proxy);
     Ok(())
 }
 
-fn unset_proxy_to_value(value: &mut Value) -> Result<(), LaneError> {
-    // remove http section from value, return while http section not exists
-    let mut http_value = match value
-        .as_table_mut()
-        .ok_or_else(|| make_failure_error("Failed to get http section."))?
-        .remove("http")
-    {
-        Some(v) => v,
+fn unset_proxy_to_document(document: &mut Document) -> Result<(), LaneError> {
+    // return while http section or proxy not exists
+    let http_table = match document.get_mut("http") {
+        Some(v) => {
+            v.as_table_like_mut().ok_or_else(|| make_failure_error("Invalid http section."))?
+        }
         None => return Err(LaneError::NothingToDo()), // no http section means no need to unset
     };
-    // remove proxy from http section, if nothing left, just return
-    match http_value.as_table_mut() {
-        Some(http_table) => {
-            http_table.remove("proxy");
-            if http_table.is_empty() {
-                return Ok(()); // nothing left
-            }
-        }
-        None => {
-            return Err(make_failure_error("Failed to get http table."));
-        }
+    if http_table.remove("proxy").is_none() {
+        return Err(LaneError::NothingToDo());
+    }
+    // remove http section if nothing left
+    if http_table.is_empty() {
+        document.remove("http");
     }
-    // insert http section back
-    value
-        .as_table_mut()
-        .ok_or_else(|| make_failure_error("Failed to replace http section."))?
-        .insert("http".into(), http_value);
     Ok(())
 }
 
-fn read_mirror_from_config(path: &Path) -> Result<Option<Mirrors>, LaneError> {
-    let content = read_to_string(path).map_err(|_| make_invalid_file_error(path))?;
-    let value: Value = content.parse().map_err(|_| make_invalid_file_error(path))?;
-    let replace_with = match value.get("source").and_then(|v| v.get("crates-io")) {
-        None => return Ok(None),
-        Some(value) => match value.get("replace-with") {
-            Some(v) => v,
-            None => return Ok(None),
-        },
-    };
-    match replace_with.as_str() {
-        None => Ok(None),
-        Some(v) => Ok(Some(Mirrors::from_str(v, true).map_err(|_| make_unknown_mirror_error(v))?)),
-    }
+fn get_registry_from_document<'a>(document: &'a Document, source: &str) -> Option<&'a str> {
+    document
+        .get("source")
+        .and_then(|v| v.get(source))
+        .and_then(|v| v.get("registry"))
+        .and_then(|v| v.as_str())
 }
 
-fn create_string_table_value(key: &str, value: &str) -> Value {
-    let mut table = Table::new();
-    table.insert(key.into(), Value::String(value.into()));
-    Value::Table(table)
+// get name of the source replacing crates-io, and its registry url
+fn read_mirror_from_config(path: &Path) -> Result<Option<(String, Option<String>)>, LaneError> {
+    let document = read_document(path)?;
+    let name = match get_replace_with_from_document(&document) {
+        None => return Ok(None),
+        Some(name) => name,
+    };
+    let registry = get_registry_from_document(&document, name).map(String::from);
+    Ok(Some((name.to_string(), registry)))
 }
 
 #[trace(logging)]
-fn set_mirror_to_value(value: &mut Value, mirror: &Mirrors) -> Result<(), LaneError> {
+fn set_mirror_to_document(document: &mut Document, mirror: &MirrorEntry) -> Result<(), LaneError> {
     // get source section
-    let mut source_value = match value.get("source") {
-        Some(v) => v.clone(),
-        None => Value::Table(Map::new()), // create if not exists
-    };
-    let source_table_value =
-        source_value.as_table_mut().ok_or_else(|| make_failure_error("Invalid source section."))?;
-
-    // insert mirror section to source and set replace-with value of crates-io to mirror
-    let mirror_name = mirror.as_ref().to_lowercase();
-    let registry = mirror.get_message().unwrap();
-    let replace_with_value = create_string_table_value("replace-with", &mirror_name);
-    let registry_value = create_string_table_value("registry", registry);
-    source_table_value.insert("crates-io".into(), replace_with_value);
-    source_table_value.insert(mirror_name, registry_value);
-
-    // insert source section back
-    value
-        .as_table_mut()
-        .ok_or_else(|| make_failure_error("Failed to insert source section."))?
-        .insert("source".into(), source_value);
+    let source_table = get_or_insert_table(document.as_table_mut(), "source")?;
+
+    // set replace-with value of crates-io to mirror and insert mirror section to source
+    let (name, registry) = (mirror.name.as_str(), mirror.url.as_str());
+    set_string(get_or_insert_table(source_table, "crates-io")?, "replace-with", name);
+    set_string(get_or_insert_table(source_table, name)?, "registry", registry);
+
+    // a registry with same index provides the token to access the mirror
+    if let Some(token) = &mirror.auth {
+        let registries_table = get_or_insert_table(document.as_table_mut(), "registries")?;
+        let registry_table = get_or_insert_table(registries_table, name)?;
+        set_string(registry_table, "index", registry);
+        set_string(registry_table, "token", token);
+    }
     Ok(())
 }
 
-fn unset_mirror_to_value(value: &mut Value) -> Result<(), LaneError> {
+fn unset_mirror_to_document(document: &mut Document) -> Result<(), LaneError> {
     // get source.crates-io section
-    let crates_io_value = match value.get_mut("source").and_then(|v| v.get_mut("crates-io")) {
+    let crates_io_table = match document.get_mut("source").and_then(|v| v.get_mut("crates-io")) {
         None => return Err(LaneError::NothingToDo()),
-        Some(v) => v,
+        Some(v) => v
+            .as_table_like_mut()
+            .ok_or_else(|| make_failure_error("Invalid source.crates-io section."))?,
     };
 
     // remove replace-with key value pair
-    crates_io_value
-        .as_table_mut()
-        .ok_or_else(|| make_failure_error("Invalid source.crates-io section."))?
-        .remove("replace-with");
-    Ok(())
+    match crates_io_table.remove("replace-with") {
+        None => Err(LaneError::NothingToDo()),
+        Some(_) => Ok(()),
+    }
 }
 
-fn get_config_file_path() -> Result<PathBuf, LaneError> {
-    let home_dir = home_dir().ok_or(LaneError::NoHomeDir())?;
-    Ok(home_dir.join(CARGO_CONFIG))
+fn get_cargo_home() -> Result<PathBuf, LaneError> {
+    match env::var_os("CARGO_HOME") {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+        _ => Ok(home_dir().ok_or(LaneError::NoHomeDir())?.join(CARGO_HOME)),
+    }
 }
 
-pub fn get_proxy() -> Option<String> {
-    let config_file = home_dir()?.join(CARGO_CONFIG);
-    read_proxy_from_config(&config_file).unwrap_or_default()
+// warn when both config files exist and set proxy or mirror differently
+fn warn_on_conflicts(config: &Path, legacy: &Path) {
+    let (config_document, legacy_document) = match (read_document(config), read_document(legacy)) {
+        (Ok(c), Ok(l)) => (c, l),
+        _ => return,
+    };
+    let conflicted = get_proxy_from_document(&config_document)
+        != get_proxy_from_document(&legacy_document)
+        || get_replace_with_from_document(&config_document)
+            != get_replace_with_from_document(&legacy_document);
+    if conflicted {
+        warn!(
+            "Both {} and {} exist with different proxy or mirror, using {} as cargo does",
+            legacy.display(),
+            config.display(),
+            legacy.display()
+        );
+    }
 }
 
-pub fn set_proxy(proxy: &str) -> Result<(), LaneError> {
-    validate_proxy_url(proxy)?;
-    let config_file = get_config_file_path()?;
-    let file_content: String = read_to_string(&config_file).unwrap_or_default();
-    let mut value: Value =
-        file_content.parse().map_err(|_| make_invalid_file_error(&config_file))?;
-    set_proxy_to_value(&mut value, proxy)?;
-    write(&config_file, value.to_string()).map_err(|_| make_write_file_error(&config_file))
+fn get_config_dir(scope: Scope) -> Result<PathBuf, LaneError> {
+    match scope {
+        Scope::Project => Ok(env::current_dir().map_err(make_failure_error)?.join(CARGO_HOME)),
+        Scope::User => get_cargo_home(),
+        // cargo has no system config, but reads config in all ancestors of current directory
+        Scope::System => Ok(PathBuf::from(SYSTEM_ROOT).join(CARGO_HOME)),
+    }
 }
 
-pub fn unset_proxy() -> Result<(), LaneError> {
-    let config_file = get_config_file_path()?;
-    let file_content: String = match read_to_string(&config_file) {
-        Err(_) => return Err(LaneError::NothingToDo()), // no file means no need to unset
-        Ok(content) => content,
-    };
-    let mut value: Value =
-        file_content.parse().map_err(|_| make_invalid_file_error(&config_file))?;
-    unset_proxy_to_value(&mut value)?;
-    write(&config_file, value.to_string()).map_err(|_| make_write_file_error(&config_file))
+// find config file like cargo does: config.toml is preferred, but legacy config is used when it
+// exists, even if config.toml exists too. Cargo reads only the legacy one then, so editing
+// config.toml would have no effect.
+fn get_config_file_path(scope: Scope) -> Result<PathBuf, LaneError> {
+    let config_dir = get_config_dir(scope)?;
+    let config = config_dir.join(CARGO_CONFIG);
+    let legacy = config_dir.join(CARGO_CONFIG_LEGACY);
+    match (config.is_file(), legacy.is_file()) {
+        (true, true) => {
+            warn_on_conflicts(&config, &legacy);
+            Ok(legacy)
+        }
+        (false, true) => Ok(legacy),
+        _ => Ok(config),
+    }
 }
 
-pub fn get_mirror() -> Result<Option<Mirrors>, LaneError> {
-    let config_file = get_config_file_path()?;
-    read_mirror_from_config(&config_file)
-}
+pub struct Cargo;
+
+impl App for Cargo {
+    fn name(&self) -> &'static str {
+        "cargo"
+    }
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::Proxy, Capability::Mirror]
+    }
+
+    fn scopes(&self) -> &'static [Scope] {
+        &[Scope::Project, Scope::User, Scope::System]
+    }
+
+    fn config_files(&self, scope: Scope) -> Vec<PathBuf> {
+        match get_config_dir(scope) {
+            Ok(dir) => vec![dir.join(CARGO_CONFIG), dir.join(CARGO_CONFIG_LEGACY)],
+            Err(_) => vec![],
+        }
+    }
+
+    fn get_proxies(&self, scope: Scope) -> Result<Vec<Proxy>, LaneError> {
+        let config_file = get_config_file_path(scope)?;
+        let proxy = read_proxy_from_config(&config_file).unwrap_or_default();
+        Ok(proxy.into_iter().map(|p| Proxy::new(scope, Some(&config_file), KEY_PROXY, p)).collect())
+    }
+
+    fn set_proxy(&self, scope: Scope, proxy: &str) -> Result<(), LaneError> {
+        validate_proxy_url(proxy)?;
+        let config_file = get_config_file_path(scope)?;
+        let file_content: String = read_file(&config_file).unwrap_or_default();
+        let mut document: Document =
+            file_content.parse().map_err(|_| make_invalid_file_error(&config_file))?;
+        set_proxy_to_document(&mut document, proxy)?;
+        write_document(&config_file, &document)
+    }
+
+    fn unset_proxy(&self, scope: Scope) -> Result<(), LaneError> {
+        let config_file = get_config_file_path(scope)?;
+        if !config_file.exists() {
+            return Err(LaneError::NothingToDo()); // no file means no need to unset
+        }
+        let mut document = read_document(&config_file)?;
+        unset_proxy_to_document(&mut document)?;
+        write_document(&config_file, &document)
+    }
+
+    fn builtin_mirrors(&self) -> Vec<MirrorEntry> {
+        mirror_entries::<Mirrors>()
+    }
+
+    fn get_mirror(&self, scope: Scope) -> Result<Option<Mirror>, LaneError> {
+        let config_file = get_config_file_path(scope)?;
+        if !config_file.exists() {
+            return Ok(None);
+        }
+        let (name, registry) = match read_mirror_from_config(&config_file)? {
+            None => return Ok(None),
+            Some(mirror) => mirror,
+        };
+        // recognize mirror by registry url first, so sections named differently are known as well,
+        // then by the name of source replacing crates-io, or report the url of an unknown mirror
+        let known = match registry.as_deref().map(|url| self.find_mirror_by_url(url)) {
+            Some(Ok(Some(mirror))) => Some(mirror),
+            _ => self.find_mirror(&name).ok(),
+        };
+        let (name, url) = match (known, registry) {
+            (Some(mirror), Some(url)) => (Some(mirror.name), url),
+            (Some(mirror), None) => (Some(mirror.name), mirror.url),
+            (None, Some(url)) => (None, url),
+            (None, None) => return Err(make_unknown_mirror_error(&name)),
+        };
+        Ok(Some(Mirror::new(scope, Some(&config_file), KEY_MIRROR, name, &url)))
+    }
+
+    fn set_mirror(&self, scope: Scope, mirror: &MirrorEntry) -> Result<(), LaneError> {
+        let config_file = get_config_file_path(scope)?;
+        let file_content: String = read_file(&config_file).unwrap_or_default();
+        let mut document: Document =
+            file_content.parse().map_err(|_| make_invalid_file_error(&config_file))?;
+        set_mirror_to_document(&mut document, mirror)?;
+        write_document(&config_file, &document)
+    }
 
-pub fn set_mirror(mirror: &Mirrors) -> Result<(), LaneError> {
-    let config_file = get_config_file_path()?;
-    let file_content: String = read_to_string(&config_file).unwrap_or_default();
-    let mut value: Value =
-        file_content.parse().map_err(|_| make_invalid_file_error(&config_file))?;
-    set_mirror_to_value(&mut value, mirror)?;
-    write(&config_file, value.to_string()).map_err(|_| make_write_file_error(&config_file))
+    fn unset_mirror(&self, scope: Scope) -> Result<(), LaneError> {
+        let config_file = get_config_file_path(scope)?;
+        if !config_file.exists() {
+            return Err(LaneError::NothingToDo()); // no file means no need to unset
+        }
+        let mut document = read_document(&config_file)?;
+        unset_mirror_to_document(&mut document)?;
+        write_document(&config_file, &document)
+    }
 }
 
-pub fn unset_mirror() -> Result<(), LaneError> {
-    let config_file = get_config_file_path()?;
-    let file_content: String = match read_to_string(&config_file) {
-        Err(_) => return Err(LaneError::NothingToDo()), // no file means no need to unset
-        Ok(content) => content,
-    };
-    let mut value: Value =
-        file_content.parse().map_err(|_| make_invalid_file_error(&config_file))?;
-    unset_mirror_to_value(&mut value)?;
-    write(&config_file, value.to_string()).map_err(|_| make_write_file_error(&config_file))
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a hand-maintained config with comments, custom ordering and spacing
+    const CONFIG: &str = r#"# my cargo config
+
+[build]
+jobs = 8          # half of the cores
+target-dir="/tmp/target"
+
+[http]
+# proxy of office network
+proxy = "http://127.0.0.1:8080"  # keep me
+timeout = 30
+
+[source.crates-io]
+replace-with = 'tuna'   # fast in china
+
+[source.tuna]
+registry = "https://mirrors.tuna.tsinghua.edu.cn/git/crates.io-index.git"
+
+[net]
+git-fetch-with-cli = true
+"#;
+
+    fn edit(content: &str, f: impl Fn(&mut Document) -> Result<(), LaneError>) -> String {
+        let mut document: Document = content.parse().unwrap();
+        f(&mut document).unwrap();
+        document.to_string()
+    }
+
+    #[test]
+    fn set_proxy_only_changes_proxy_value() {
+        let result = edit(CONFIG, |d| set_proxy_to_document(d, "socks5://10.0.0.1:1080"));
+        let expected = CONFIG.replace("http://127.0.0.1:8080", "socks5://10.0.0.1:1080");
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn unset_proxy_only_removes_proxy_line() {
+        // comment lines right above a key belong to the key
+        let result = edit(CONFIG, unset_proxy_to_document);
+        let removed = "# proxy of office network\nproxy = \"http://127.0.0.1:8080\"  # keep me\n";
+        let expected = CONFIG.replace(removed, "");
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn unset_proxy_removes_empty_http_section() {
+        let content = "[build]\njobs = 8\n\n[http]\nproxy = \"http://127.0.0.1:8080\"\n";
+        assert_eq!(edit(content, unset_proxy_to_document), "[build]\njobs = 8\n");
+    }
+
+    #[test]
+    fn unset_proxy_without_proxy_is_nothing_to_do() {
+        let mut document: Document = "[build]\njobs = 8\n".parse().unwrap();
+        assert_eq!(unset_proxy_to_document(&mut document), Err(LaneError::NothingToDo()));
+    }
+
+    #[test]
+    fn set_proxy_appends_http_section() {
+        let result =
+            edit("# empty\n[build]\njobs = 8\n", |d| set_proxy_to_document(d, "http://p:1"));
+        assert_eq!(result, "# empty\n[build]\njobs = 8\n\n[http]\nproxy = \"http://p:1\"\n");
+    }
+
+    #[test]
+    fn set_mirror_only_changes_mirror_keys() {
+        // new mirror section is placed after other sources
+        let result = edit(CONFIG, |d| set_mirror_to_document(d, &mirror_entry(&Mirrors::Ustc)));
+        let ustc = "\n[source.ustc]\nregistry = \"http://mirrors.ustc.edu.cn/crates.io-index\"\n";
+        let expected =
+            CONFIG.replace("'tuna'", "\"ustc\"").replace("\n[net]", &format!("{}\n[net]", ustc));
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn set_mirror_to_empty_config() {
+        let result = edit("", |d| set_mirror_to_document(d, &mirror_entry(&Mirrors::Ustc)));
+        let expected = "[source.crates-io]\nreplace-with = \"ustc\"\n\n[source.ustc]\nregistry = \"http://mirrors.ustc.edu.cn/crates.io-index\"\n";
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn set_sparse_mirror() {
+        let result = edit("", |d| set_mirror_to_document(d, &mirror_entry(&Mirrors::UstcSparse)));
+        let expected = "[source.crates-io]\nreplace-with = \"ustc-sparse\"\n\n[source.ustc-sparse]\nregistry = \"sparse+https://mirrors.ustc.edu.cn/crates.io-index/\"\n";
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn set_mirror_with_auth() {
+        let mirror = MirrorEntry {
+            name: "corp".into(),
+            url: "sparse+https://corp/index/".into(),
+            auth: Some("secret".into()),
+        };
+        let result = edit("", |d| set_mirror_to_document(d, &mirror));
+        let expected = "[source.crates-io]\nreplace-with = \"corp\"\n\n[source.corp]\nregistry = \"sparse+https://corp/index/\"\n\n[registries.corp]\nindex = \"sparse+https://corp/index/\"\ntoken = \"secret\"\n";
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn recognize_mirror_by_registry() {
+        let find = |url| find_mirror_by_url(mirror_entries::<Mirrors>(), url).map(|m| m.name);
+        assert_eq!(find("https://mirrors.ustc.edu.cn/crates.io-index/"), Some("ustc".into()));
+        assert_eq!(
+            find("sparse+https://mirrors.ustc.edu.cn/crates.io-index"),
+            Some("ustc-sparse".into())
+        );
+        assert_eq!(find("sparse+https://rsproxy.cn/index/"), Some("rsproxy-sparse".into()));
+        assert_eq!(find("https://example.com/index"), None);
+    }
+
+    #[test]
+    fn unset_mirror_only_removes_replace_with() {
+        let result = edit(CONFIG, unset_mirror_to_document);
+        let expected = CONFIG.replace("replace-with = 'tuna'   # fast in china\n", "");
+        assert_eq!(result, expected);
+    }
 }
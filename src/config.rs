@@ -0,0 +1,231 @@
+// lane's own config, in $XDG_CONFIG_HOME/lane/config.toml (~/.config/lane/config.toml by default)
+//
+// [mirrors.npm.corp]
+// url = "https://nexus.corp/repository/npm/"
+// auth = "token"    # optional
+//
+// [profiles.office]
+// proxy = "http://proxy.corp:8080"
+// no-proxy = ["localhost", ".corp"]
+// mirrors = { cargo = "corp", npm = "taobao" }
+
+use crate::app::MirrorEntry;
+use crate::error::*;
+use crate::utils::*;
+use dirs::home_dir;
+use log::warn;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+use toml_edit::{Array, Document, InlineTable, Item, Table, Value};
+
+const LANE_CONFIG: &str = "lane/config.toml";
+const DEFAULT_CONFIG_HOME: &str = ".config";
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Config {
+    /// User defined mirrors, by app name and then mirror name
+    #[serde(default)]
+    pub mirrors: BTreeMap<String, BTreeMap<String, MirrorEntry>>,
+    /// Named profiles applied by `lane use`
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Settings of a network, applied to all apps at once
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct Profile {
+    /// Proxy of all apps, `None` to clear proxies
+    pub proxy: Option<String>,
+    /// Hosts accessed without proxy
+    #[serde(default, rename = "no-proxy")]
+    pub no_proxy: Vec<String>,
+    /// Mirror name or url by app name, apps not listed keep their mirrors
+    #[serde(default)]
+    pub mirrors: BTreeMap<String, String>,
+}
+
+/// Path of lane config, `LANE_CONFIG` env overrides the default one
+pub fn get_config_file_path() -> Result<PathBuf, LaneError> {
+    if let Some(file) = env::var_os("LANE_CONFIG").filter(|f| !f.is_empty()) {
+        return Ok(PathBuf::from(file));
+    }
+    let config_home = match env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => home_dir().ok_or(LaneError::NoHomeDir())?.join(DEFAULT_CONFIG_HOME),
+    };
+    Ok(config_home.join(LANE_CONFIG))
+}
+
+impl Config {
+    /// Load lane config, a missing file is the same as an empty one
+    pub fn load() -> Result<Config, LaneError> {
+        Config::load_from(&get_config_file_path()?)
+    }
+
+    /// Load lane config of the path, a missing file is the same as an empty one
+    pub fn load_from(path: &Path) -> Result<Config, LaneError> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let content = read_to_string(path).map_err(|_| make_invalid_file_error(path))?;
+        Config::parse(&content).map_err(|_| make_invalid_file_error(path))
+    }
+
+    pub fn parse(content: &str) -> Result<Config, toml_edit::de::Error> {
+        toml_edit::de::from_str(content)
+    }
+
+    /// User defined mirrors of the app, those with invalid urls are ignored
+    pub fn mirrors_of(&self, app: &str) -> Vec<MirrorEntry> {
+        let mirrors = match self.mirrors.get(app) {
+            None => return vec![],
+            Some(mirrors) => mirrors,
+        };
+        let is_valid = |(name, mirror): &(&String, &MirrorEntry)| {
+            let valid = validate_mirror_url(&mirror.url).is_ok();
+            if !valid {
+                warn!("Mirror {} of {} has invalid url {}, ignored", name, app, mirror.url);
+            }
+            valid
+        };
+        mirrors
+            .iter()
+            .filter(is_valid)
+            .map(|(name, mirror)| MirrorEntry { name: name.clone(), ..mirror.clone() })
+            .collect()
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&Profile, LaneError> {
+        self.profiles.get(name).ok_or_else(|| make_unknown_profile_error(name))
+    }
+}
+
+/// User defined mirrors followed by built-in ones, which are overridden by user defined ones of the
+/// same name
+pub fn merge_mirrors(mut mirrors: Vec<MirrorEntry>, builtin: Vec<MirrorEntry>) -> Vec<MirrorEntry> {
+    for builtin in builtin {
+        if !mirrors.iter().any(|m| m.name.eq_ignore_ascii_case(&builtin.name)) {
+            mirrors.push(builtin);
+        }
+    }
+    mirrors
+}
+
+fn profile_to_table(profile: &Profile) -> Table {
+    let mut table = Table::new();
+    if let Some(proxy) = &profile.proxy {
+        table["proxy"] = toml_edit::value(proxy.as_str());
+    }
+    if !profile.no_proxy.is_empty() {
+        table["no-proxy"] = toml_edit::value(profile.no_proxy.iter().collect::<Array>());
+    }
+    if !profile.mirrors.is_empty() {
+        let mirrors: InlineTable = profile
+            .mirrors
+            .iter()
+            .map(|(app, mirror)| (app, Value::from(mirror.as_str())))
+            .collect();
+        table["mirrors"] = toml_edit::value(mirrors);
+    }
+    table
+}
+
+fn read_config_document() -> Result<(PathBuf, Document), LaneError> {
+    let path = get_config_file_path()?;
+    let document = match path.exists() {
+        true => read_document(&path)?,
+        false => Document::new(),
+    };
+    Ok((path, document))
+}
+
+/// Add a profile to lane config, replacing the one with the same name. Other parts of the config
+/// are kept as is.
+pub fn save_profile(name: &str, profile: &Profile) -> Result<(), LaneError> {
+    let (path, mut document) = read_config_document()?;
+    let profiles = document
+        .entry("profiles")
+        .or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        })
+        .as_table_mut()
+        .ok_or_else(|| make_invalid_file_error(&path))?;
+    profiles.insert(name, Item::Table(profile_to_table(profile)));
+    write_document(&path, &document)
+}
+
+pub fn remove_profile(name: &str) -> Result<(), LaneError> {
+    let (path, mut document) = read_config_document()?;
+    let profiles = match document.get_mut("profiles").and_then(Item::as_table_mut) {
+        Some(profiles) => profiles,
+        None => return Err(make_unknown_profile_error(name)),
+    };
+    profiles.remove(name).ok_or_else(|| make_unknown_profile_error(name))?;
+    if profiles.is_empty() {
+        document.remove("profiles");
+    }
+    write_document(&path, &document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mirror(name: &str, url: &str) -> MirrorEntry {
+        MirrorEntry { name: name.to_string(), url: url.to_string(), auth: None }
+    }
+
+    #[test]
+    fn parse_user_mirrors() {
+        let content = "[mirrors.npm.corp]\nurl = \"https://nexus.corp/repository/npm/\"\n\
+                       auth = \"token\"\n[mirrors.npm.broken]\nurl = \"nexus.corp/npm\"\n";
+        let config = Config::parse(content).unwrap();
+        let corp = MirrorEntry {
+            auth: Some("token".to_string()),
+            ..mirror("corp", "https://nexus.corp/repository/npm/")
+        };
+        // the mirror with an invalid url is ignored
+        assert_eq!(config.mirrors_of("npm"), [corp]);
+        assert_eq!(config.mirrors_of("cargo"), []);
+        // a mirror without url is an invalid config
+        assert!(Config::parse("[mirrors.npm.corp]\nauth = \"token\"\n").is_err());
+        // so are mirrors of the same name
+        let duplicated = "[mirrors.npm.corp]\nurl = \"https://a.corp/\"\n\
+                          [mirrors.npm.corp]\nurl = \"https://b.corp/\"\n";
+        assert!(Config::parse(duplicated).is_err());
+    }
+
+    #[test]
+    fn user_mirrors_override_builtin_ones() {
+        let user =
+            vec![mirror("Taobao", "https://npm.corp/"), mirror("corp", "https://nexus.corp/")];
+        let builtin = vec![
+            mirror("taobao", "https://registry.npmmirror.com/"),
+            mirror("huawei", "https://repo.huaweicloud.com/repository/npm/"),
+        ];
+        let names: Vec<(String, String)> =
+            merge_mirrors(user, builtin).into_iter().map(|m| (m.name, m.url)).collect();
+        let expected = [
+            ("Taobao", "https://npm.corp/"),
+            ("corp", "https://nexus.corp/"),
+            ("huawei", "https://repo.huaweicloud.com/repository/npm/"),
+        ]
+        .map(|(name, url)| (name.to_string(), url.to_string()));
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn load_missing_config() {
+        let path = env::temp_dir().join(format!("lane-test-config-{}.toml", std::process::id()));
+        let config = Config::load_from(&path).unwrap();
+        assert!(config.mirrors.is_empty() && config.profiles.is_empty());
+        assert_eq!(config.profile("office"), Err(make_unknown_profile_error("office")));
+    }
+}
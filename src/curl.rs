@@ -1,9 +1,10 @@
 // curl: https://everything.curl.dev/cmdline/configfile
 
+use crate::app::*;
 use crate::error::*;
+use crate::utils::*;
 use dirs::home_dir;
 use std::env;
-use std::fs::{read_to_string, write};
 use std::path::{Path, PathBuf};
 use trace::trace;
 
@@ -13,6 +14,7 @@ const CURL_CONFIG_FILE: &str = ".curlrc";
 #[cfg(target_os = "windows")]
 const CURL_CONFIG_FILE2: &str = "_curlrc";
 const CURL_KEY_PROXY: &str = "proxy";
+const CURL_KEY_NO_PROXY: &str = "noproxy";
 
 fn test_file_existence(dir_option: &Option<PathBuf>) -> Option<PathBuf> {
     macro_rules! return_when_file_exists {
@@ -40,7 +42,7 @@ fn get_config_file_list() -> Vec<PathBuf> {
 
 #[cfg(not(target_os = "windows"))]
 fn get_config_file_list() -> Vec<PathBuf> {
-    vec![
+    [
         env::var("CURL_HOME").ok().map(PathBuf::from),
         env::var("XDG_CONFIG_HOME").ok().map(PathBuf::from),
         home_dir(),
@@ -50,6 +52,14 @@ fn get_config_file_list() -> Vec<PathBuf> {
     .collect()
 }
 
+// curl reads no config of projects or the system, only `.curlrc` of the user
+fn check_scope(scope: Scope) -> Result<(), LaneError> {
+    match scope {
+        Scope::User => Ok(()),
+        _ => Err(make_unsupported_scope_error("curl", scope)),
+    }
+}
+
 #[trace(logging)]
 fn parse_line(line: &str) -> Option<(String, String)> {
     // refer to parseconfig() in https://github.com/curl/curl/blob/master/src/tool_parsecfg.c
@@ -85,16 +95,16 @@ fn push_line(target: &mut String, line: &str) {
     target.push('\n');
 }
 
-fn push_proxy_line(target: &mut String, proxy: &Option<&str>) {
-    if let Some(url) = proxy {
-        let new_line = format!("proxy = \"{}\"", *url);
+fn push_option_line(target: &mut String, key: &str, value: &Option<&str>) {
+    if let Some(value) = value {
+        let new_line = format!("{} = \"{}\"", key, *value);
         push_line(target, &new_line);
     }
 }
 
 #[trace(logging)]
 fn get_proxy_from_config(path: &Path) -> Result<Option<String>, LaneError> {
-    let file_content = read_to_string(path).map_err(|_| make_invalid_file_error(path))?;
+    let file_content = read_file(path).map_err(|_| make_invalid_file_error(path))?;
     for line in file_content.lines() {
         if let Some((option, param)) = parse_line(line) {
             if option.as_str() == CURL_KEY_PROXY {
@@ -106,76 +116,153 @@ fn get_proxy_from_config(path: &Path) -> Result<Option<String>, LaneError> {
 }
 
 #[trace(logging)]
-fn update_proxy_to_file(
+fn update_option_to_file(
     path: &Path,
-    proxy: &Option<&str>,
+    key: &str,
+    value: &Option<&str>,
     force: bool,
-) -> Result<(), anyhow::Error> {
+) -> Result<bool, anyhow::Error> {
     // when not force, write to file only when file read to string successfully
     // when force, generated a new file when file not found
     let file_content: String = match force {
-        false => read_to_string(path)?,
-        true => read_to_string(path).ok().unwrap_or_default(),
+        false => read_file(path)?,
+        true => read_file(path).ok().unwrap_or_default(),
     };
     let mut updated = false;
     let mut target = String::new();
     for line in file_content.lines() {
-        // after updated, just push rest lines into target
-        if updated {
+        // after updated, just push rest lines into target, but keep removing the option on unset
+        if updated && value.is_some() {
             push_line(&mut target, line);
             continue;
         }
         // parse line and update
         match parse_line(line) {
             Some((option, _)) => {
-                if option.as_str() != CURL_KEY_PROXY {
+                if option.as_str() != key {
                     push_line(&mut target, line);
                     continue;
                 }
-                // update url or skip this line as url_option implied
-                push_proxy_line(&mut target, proxy);
+                // update value or skip this line as value option implied
+                push_option_line(&mut target, key, value);
                 updated = true;
             }
             _ => push_line(&mut target, line),
         }
     }
     if !updated {
+        // nothing to remove when unset
+        if value.is_none() {
+            return Ok(false);
+        }
         // not found in lines
-        push_proxy_line(&mut target, proxy);
+        push_option_line(&mut target, key, value);
     }
     // write back to file
-    write(path, target)?;
-    Ok(())
-}
-
-pub fn get_proxy() -> Option<String> {
-    for file in get_config_file_list() {
-        if let Ok(Some(proxy)) = get_proxy_from_config(&file) {
-            return Some(proxy);
-        }
-    }
-    None
+    write_file(path, &target)?;
+    Ok(updated)
 }
 
-pub fn set_proxy(proxy: &str) -> Result<(), LaneError> {
-    validate_proxy_url(proxy)?;
-    let proxy_option = Some(proxy);
+fn set_option(key: &str, value: &str) -> Result<(), LaneError> {
+    let value_option = Some(value);
     for file in get_config_file_list() {
-        if update_proxy_to_file(&file, &proxy_option, false).is_ok() {
+        if update_option_to_file(&file, key, &value_option, false).is_ok() {
             return Ok(());
         }
     }
-    // When failed to update existed proxy, force create a new config file
+    // When failed to update existed config file, force create a new one
     let home_dir = home_dir().ok_or(LaneError::NoHomeDir())?;
     let default_config = home_dir.join(CURL_CONFIG_FILE);
-    update_proxy_to_file(&default_config, &proxy_option, true).map_err(make_failure_error)
+    update_option_to_file(&default_config, key, &value_option, true)
+        .map(|_| ())
+        .map_err(make_failure_error)
 }
 
-pub fn unset_proxy() -> Result<(), LaneError> {
+fn unset_option(key: &str) -> Result<(), LaneError> {
+    let mut removed = false;
     for file in get_config_file_list() {
-        if update_proxy_to_file(&file, &None, false).is_ok() {
-            return Ok(());
+        removed |= update_option_to_file(&file, key, &None, false).map_err(make_failure_error)?;
+    }
+    match removed {
+        true => Ok(()),
+        false => Err(LaneError::NothingToDo()),
+    }
+}
+
+pub struct Curl;
+
+impl App for Curl {
+    fn name(&self) -> &'static str {
+        "curl"
+    }
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::Proxy, Capability::NoProxy]
+    }
+
+    fn scopes(&self) -> &'static [Scope] {
+        &[Scope::User]
+    }
+
+    fn get_proxies(&self, scope: Scope) -> Result<Vec<Proxy>, LaneError> {
+        check_scope(scope)?;
+        for file in get_config_file_list() {
+            if let Ok(Some(proxy)) = get_proxy_from_config(&file) {
+                return Ok(vec![Proxy::new(scope, Some(&file), CURL_KEY_PROXY, proxy)]);
+            }
+        }
+        Ok(vec![])
+    }
+
+    fn config_files(&self, scope: Scope) -> Vec<PathBuf> {
+        if check_scope(scope).is_err() {
+            return vec![];
         }
+        let mut files = get_config_file_list();
+        files.extend(home_dir().map(|dir| dir.join(CURL_CONFIG_FILE)));
+        files
+    }
+
+    fn set_proxy(&self, scope: Scope, proxy: &str) -> Result<(), LaneError> {
+        check_scope(scope)?;
+        validate_proxy_url(proxy)?;
+        set_option(CURL_KEY_PROXY, proxy)
+    }
+
+    fn unset_proxy(&self, scope: Scope) -> Result<(), LaneError> {
+        check_scope(scope)?;
+        unset_option(CURL_KEY_PROXY)
+    }
+
+    fn set_no_proxy(&self, scope: Scope, hosts: &[String]) -> Result<(), LaneError> {
+        check_scope(scope)?;
+        set_option(CURL_KEY_NO_PROXY, &hosts.join(","))
+    }
+
+    fn unset_no_proxy(&self, scope: Scope) -> Result<(), LaneError> {
+        check_scope(scope)?;
+        unset_option(CURL_KEY_NO_PROXY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unset_option_in_file() {
+        let dir = env::temp_dir().join(format!("lane-test-curl-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(CURL_CONFIG_FILE);
+        let content = "# curl\nproxy = \"http://a:1\"\nsilent\nproxy = \"http://b:2\"\n";
+        std::fs::write(&file, content).unwrap();
+
+        assert!(update_option_to_file(&file, CURL_KEY_PROXY, &None, false).unwrap());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "# curl\nsilent\n");
+        // nothing removed and the file is left untouched
+        assert!(!update_option_to_file(&file, CURL_KEY_PROXY, &None, false).unwrap());
+        assert!(!update_option_to_file(&file, CURL_KEY_NO_PROXY, &None, false).unwrap());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "# curl\nsilent\n");
+        std::fs::remove_dir_all(&dir).unwrap();
     }
-    Err(LaneError::NothingToDo())
 }
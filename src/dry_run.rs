@@ -0,0 +1,59 @@
+// Dry run: config files are not written, but collected as planned changes. Files written in a dry
+// run are read back with the planned content, so later steps of the same operation see the changes
+// of former ones.
+
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+};
+
+/// New content of a config file lane would write
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub path: PathBuf,
+    /// `None` when the file doesn't exist
+    pub before: Option<String>,
+    /// `None` when the file is removed
+    pub after: Option<String>,
+}
+
+thread_local! {
+    static PLANNED: RefCell<Option<Vec<Change>>> = const { RefCell::new(None) };
+}
+
+/// Run the operation without changing anything, and return the changes it would make
+pub fn run<T>(op: impl FnOnce() -> T) -> (T, Vec<Change>) {
+    PLANNED.with(|planned| planned.replace(Some(vec![])));
+    let result = op();
+    let changes = PLANNED.with(|planned| planned.replace(None)).unwrap_or_default();
+    (result, changes)
+}
+
+pub(crate) fn is_active() -> bool {
+    PLANNED.with(|planned| planned.borrow().is_some())
+}
+
+/// Planned content of the file, `None` when the file is not planned to change, and `Some(None)`
+/// when it's planned to be removed
+pub(crate) fn planned_content(path: &Path) -> Option<Option<String>> {
+    PLANNED.with(|planned| {
+        planned
+            .borrow()
+            .iter()
+            .flatten()
+            .find(|change| change.path == path)
+            .map(|c| c.after.clone())
+    })
+}
+
+pub(crate) fn plan_file(path: PathBuf, before: Option<String>, after: Option<String>) {
+    PLANNED.with(|planned| {
+        if let Some(changes) = planned.borrow_mut().as_mut() {
+            // keep the original content of a file written several times
+            match changes.iter_mut().find(|change| change.path == path) {
+                Some(change) => change.after = after,
+                None => changes.push(Change { path, before, after }),
+            }
+        }
+    })
+}
@@ -1,8 +1,9 @@
-use std::{fmt::Display, path::Path, str::FromStr};
+use crate::app::{Capability, Scope};
+use std::{fmt::Display, io, path::Path, str::FromStr};
 use thiserror::Error;
 use url::Url;
 
-#[derive(Error, Clone, Debug)]
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
 pub enum LaneError {
     #[error("Config file {0} doesn't exist or content error.")]
     InvalidFile(String),
@@ -16,33 +17,61 @@ pub enum LaneError {
     NoHomeDir(),
     #[error("Unknown mirror {0}")]
     UnknownMirror(String),
+    #[error("Unknown profile {0}")]
+    UnknownProfile(String),
+    #[error("Invalid mirror url: {0}.")]
+    InvalidMirrorUrl(String),
+    /// No longer returned since lane edits config files instead of running other programs
+    #[deprecated(note = "lane doesn't run other programs any more")]
     #[error("Failed to exec command")]
     CommandFailed(),
+    #[error("Invalid package scope: {0}.")]
+    InvalidPackageScope(String),
     #[error("Failed. Reason: {0}")]
     Failure(String),
+    #[error("{0} doesn't support {1}.")]
+    Unsupported(String, String),
+    #[error("Permission denied writing {0}, please retry as an administrator.")]
+    PermissionDenied(String),
 }
 
-pub fn make_invalid_file_error(path: &Path) -> LaneError {
+pub(crate) fn make_invalid_file_error(path: &Path) -> LaneError {
     LaneError::InvalidFile(path.to_str().unwrap_or_default().to_string())
 }
 
-pub fn make_write_file_error(path: &Path) -> LaneError {
-    LaneError::WriteFailure(path.to_str().unwrap_or_default().to_string())
+pub(crate) fn make_write_file_error(path: &Path, error: &io::Error) -> LaneError {
+    let path = path.to_str().unwrap_or_default().to_string();
+    match error.kind() {
+        io::ErrorKind::PermissionDenied => LaneError::PermissionDenied(path),
+        _ => LaneError::WriteFailure(path),
+    }
 }
 
-pub fn make_unknown_mirror_error(mirror: &str) -> LaneError {
+pub(crate) fn make_unknown_mirror_error(mirror: &str) -> LaneError {
     LaneError::UnknownMirror(mirror.to_string())
 }
 
-pub fn make_command_failed_error(_error: impl Display) -> LaneError {
-    LaneError::CommandFailed()
+pub(crate) fn make_unknown_profile_error(profile: &str) -> LaneError {
+    LaneError::UnknownProfile(profile.to_string())
 }
 
-pub fn make_failure_error(reason: impl Display) -> LaneError {
+pub(crate) fn make_failure_error(reason: impl Display) -> LaneError {
     LaneError::Failure(reason.to_string())
 }
 
+pub(crate) fn make_unsupported_error(app: &str, capability: Capability) -> LaneError {
+    LaneError::Unsupported(app.to_string(), capability.as_ref().to_lowercase())
+}
+
+pub(crate) fn make_unsupported_scope_error(app: &str, scope: Scope) -> LaneError {
+    LaneError::Unsupported(app.to_string(), format!("{} scope", scope.as_ref()))
+}
+
 pub fn validate_proxy_url(url: &str) -> Result<(), LaneError> {
     let _ = Url::from_str(url).map_err(|_| LaneError::InvalidProxyUrl(url.to_string()))?;
     Ok(())
 }
+
+pub fn validate_mirror_url(url: &str) -> Result<Url, LaneError> {
+    Url::from_str(url).map_err(|_| LaneError::InvalidMirrorUrl(url.to_string()))
+}
@@ -1,59 +1,709 @@
+// git: https://git-scm.com/docs/git-config#FILES
+
+use crate::app::*;
 use crate::error::*;
+use crate::gitconfig::{Entry, GitConfig};
+use crate::sshconfig::{self, SshConfig};
 use crate::utils::*;
-use git_config::parser::Key;
-use std::{fmt::Display, str::from_utf8};
+use dirs::home_dir;
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 
 const GIT_CONFIG: &str = ".gitconfig";
+const GIT_XDG_CONFIG: &str = "git/config";
+const DEFAULT_CONFIG_HOME: &str = ".config";
+const GIT_DIR: &str = ".git";
+const GIT_LOCAL_CONFIG: &str = "config";
+const GIT_SYSTEM_CONFIG: &str = "/etc/gitconfig";
 const SECTION_HTTP: &str = "http";
 const KEY_PROXY: &str = "proxy";
+// same as git
+const MAX_INCLUDE_DEPTH: usize = 10;
+const SSH_CONFIG: &str = ".ssh/config";
+const SSH_SYSTEM_CONFIG: &str = "/etc/ssh/ssh_config";
+const SSH_SCHEME: &str = "ssh://";
+const SECTION_CORE: &str = "core";
+const KEY_GIT_PROXY: &str = "gitProxy";
+const GIT_SCHEME: &str = "git://";
+const DEFAULT_DATA_HOME: &str = ".local/share";
+const LANE_DATA: &str = "lane";
+const SYSTEM_SCRIPT_DIR: &str = "/usr/local/share/lane";
+#[cfg(not(target_os = "windows"))]
+const SCRIPT_TEMPLATE: (&str, &str, &str, &str) = ("#!/bin/sh\n", "# ", "exec ", "");
+#[cfg(target_os = "windows")]
+const SCRIPT_TEMPLATE: (&str, &str, &str, &str) = ("@echo off\r\n", "rem ", "", ".bat");
+const SCRIPT_PREFIX: &str = "git-proxy-";
+const SCRIPT_MARKER: &str = "git protocol proxy generated by lane: ";
+
+// find .git dir of the repository containing current directory, .git could also be a file
+// containing "gitdir: <path>" for worktrees and submodules
+fn find_git_dir() -> Result<PathBuf, LaneError> {
+    let current_dir = env::current_dir().map_err(make_failure_error)?;
+    let dot_git = current_dir
+        .ancestors()
+        .map(|dir| dir.join(GIT_DIR))
+        .find(|dir| dir.exists())
+        .ok_or_else(|| make_failure_error("Not in a git repository"))?;
+    if dot_git.is_dir() {
+        return Ok(dot_git);
+    }
+    let content = read_file(&dot_git).map_err(|_| make_invalid_file_error(&dot_git))?;
+    let git_dir =
+        content.trim().strip_prefix("gitdir:").ok_or_else(|| make_invalid_file_error(&dot_git))?;
+    Ok(dot_git.parent().unwrap_or(Path::new("")).join(git_dir.trim()))
+}
+
+// config of a worktree is in the common dir shared by all worktrees
+fn get_common_dir(git_dir: &Path) -> PathBuf {
+    match read_file(&git_dir.join("commondir")) {
+        Ok(common_dir) => git_dir.join(common_dir.trim()),
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+fn get_xdg_config_path() -> Result<PathBuf, LaneError> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => home_dir().ok_or(LaneError::NoHomeDir())?.join(DEFAULT_CONFIG_HOME),
+    };
+    Ok(config_home.join(GIT_XDG_CONFIG))
+}
+
+// config file written in the scope, like `git config` does: ~/.gitconfig is preferred for user scope,
+// but the xdg one is used when only it exists
+fn get_config_file_path(scope: Scope) -> Result<PathBuf, LaneError> {
+    match scope {
+        Scope::Project => Ok(get_common_dir(&find_git_dir()?).join(GIT_LOCAL_CONFIG)),
+        Scope::User => {
+            if let Some(file) = env::var_os("GIT_CONFIG_GLOBAL").filter(|f| !f.is_empty()) {
+                return Ok(PathBuf::from(file));
+            }
+            let config = home_dir().ok_or(LaneError::NoHomeDir())?.join(GIT_CONFIG);
+            let xdg_config = get_xdg_config_path()?;
+            match !config.exists() && xdg_config.exists() {
+                true => Ok(xdg_config),
+                false => Ok(config),
+            }
+        }
+        Scope::System => match env::var_os("GIT_CONFIG_SYSTEM") {
+            Some(file) if !file.is_empty() => Ok(PathBuf::from(file)),
+            _ => Ok(PathBuf::from(GIT_SYSTEM_CONFIG)),
+        },
+    }
+}
+
+// config files read in the scope, latter ones override former ones
+fn get_config_files(scope: Scope) -> Result<Vec<PathBuf>, LaneError> {
+    match scope {
+        Scope::User if env::var_os("GIT_CONFIG_GLOBAL").is_none_or(|f| f.is_empty()) => Ok(vec![
+            get_xdg_config_path()?,
+            home_dir().ok_or(LaneError::NoHomeDir())?.join(GIT_CONFIG),
+        ]),
+        Scope::System if env::var_os("GIT_CONFIG_NOSYSTEM").is_some_and(|v| v != "0") => Ok(vec![]),
+        _ => Ok(vec![get_config_file_path(scope)?]),
+    }
+}
+
+fn read_config(path: &Path) -> Result<GitConfig, LaneError> {
+    if !path.exists() {
+        return Ok(GitConfig::parse(""));
+    }
+    let content = read_file(path).map_err(|_| make_invalid_file_error(path))?;
+    Ok(GitConfig::parse(&content))
+}
 
-//TODO: config for git protocol needs socks5 proxy, and should be setup in .ssh/config
+// match path with a wildcard pattern, where "*" doesn't match "/" but "**" does
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // "**/" matches zero or more dirs
+            let zero_dirs = rest.strip_prefix(&['/']).is_some_and(|r| glob_match(r, text));
+            zero_dirs || (0..=text.len()).any(|i| glob_match(rest, &text[i..]))
+        }
+        ['*', rest @ ..] => {
+            for i in 0..=text.len() {
+                if glob_match(rest, &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        ['?', rest @ ..] => text.first().is_some_and(|c| *c != '/') && glob_match(rest, &text[1..]),
+        [c, rest @ ..] => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
 
-pub struct Proxy {
-    subsection: Option<String>,
-    proxy: String,
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().to_string(),
+        _ => path.to_string(),
+    }
 }
 
-impl Display for Proxy {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self.subsection {
-            Some(sub) => write!(f, "\"{}\": {}", sub, &self.proxy),
-            None => write!(f, "{}", &self.proxy),
+// condition of includeIf, refer to https://git-scm.com/docs/git-config#_conditional_includes
+fn include_condition_matches(config_path: &Path, condition: &str) -> bool {
+    let (kind, pattern) = match condition.split_once(':') {
+        Some(split) => split,
+        None => return false,
+    };
+    let (text, mut pattern) = match kind {
+        "gitdir" | "gitdir/i" => {
+            let git_dir =
+                match find_git_dir().and_then(|d| d.canonicalize().map_err(make_failure_error)) {
+                    Ok(dir) => dir,
+                    Err(_) => return false,
+                };
+            let pattern = match pattern.strip_prefix("./") {
+                Some(rest) => config_path
+                    .parent()
+                    .unwrap_or(Path::new(""))
+                    .join(rest)
+                    .to_string_lossy()
+                    .to_string(),
+                None => expand_home(pattern),
+            };
+            let pattern = match pattern.starts_with('/') {
+                true => pattern,
+                false => format!("**/{}", pattern),
+            };
+            (git_dir.to_string_lossy().to_string(), pattern)
         }
+        "onbranch" => {
+            let head =
+                find_git_dir().and_then(|d| read_file(&d.join("HEAD")).map_err(make_failure_error));
+            let branch =
+                head.ok().and_then(|h| h.trim().strip_prefix("ref: refs/heads/").map(String::from));
+            match branch {
+                Some(branch) => (branch, pattern.to_string()),
+                None => return false,
+            }
+        }
+        // hasconfig:remote.*.url is not supported
+        _ => return false,
+    };
+    if pattern.ends_with('/') {
+        pattern += "**";
+    }
+    let (text, pattern) = match kind {
+        "gitdir/i" => (text.to_lowercase(), pattern.to_lowercase()),
+        _ => (text, pattern),
+    };
+    glob_match(&pattern.chars().collect::<Vec<_>>(), &text.chars().collect::<Vec<_>>())
+}
+
+// entries of the file, and entries of files included by it at the place of the include, as git
+// reads them. `files` collects the file and all files included by it.
+fn read_entries(
+    path: &Path,
+    depth: usize,
+    entries: &mut Vec<(PathBuf, Entry)>,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), LaneError> {
+    // missing files, including missing included ones, are ignored as git does
+    if !path.is_file() {
+        return Ok(());
+    }
+    files.push(path.to_path_buf());
+    for entry in read_config(path)?.entries() {
+        let included = match (entry.section.as_str(), &entry.subsection) {
+            ("include", None) => true,
+            ("includeif", Some(condition)) => include_condition_matches(path, condition),
+            _ => false,
+        };
+        if included && entry.key.eq_ignore_ascii_case("path") {
+            if depth >= MAX_INCLUDE_DEPTH {
+                return Err(make_failure_error(format!("Too deep includes in {}", path.display())));
+            }
+            let include = path.parent().unwrap_or(Path::new("")).join(expand_home(&entry.value));
+            read_entries(&include, depth + 1, entries, files)?;
+        }
+        entries.push((path.to_path_buf(), entry));
+    }
+    Ok(())
+}
+
+// entries of the scope in the order git reads them, and files containing them
+type ScopeEntries = (Vec<(PathBuf, Entry)>, Vec<PathBuf>);
+
+fn read_scope(scope: Scope) -> Result<ScopeEntries, LaneError> {
+    let (mut entries, mut files) = (vec![], vec![]);
+    for path in get_config_files(scope)? {
+        read_entries(&path, 0, &mut entries, &mut files)?;
     }
+    Ok((entries, files))
 }
 
-pub fn get_proxies() -> Result<Vec<Proxy>, LaneError> {
-    let mut result: Vec<Proxy> = vec![];
-    let home_dir = dirs::home_dir().ok_or(LaneError::NoHomeDir())?;
-    let git_config_file = home_dir.join(GIT_CONFIG);
-    let file = git_config::File::open(&git_config_file)
-        .map_err(|_| make_invalid_file_error(&git_config_file))?;
-    // TODO: git/ssh protocol not implemented yet
-    let sections = file.sections_by_name_with_header(SECTION_HTTP);
-    for (header, body) in sections {
-        let proxy = body.value(&Key::from(KEY_PROXY));
-        if let Some(value) = proxy {
-            result.push(Proxy {
-                subsection: header.subsection_name.as_ref().map(|s| s.to_string()),
-                proxy: from_utf8(&value)
-                    .map_err(|_| make_failure_error("Invalid git config file content"))?
-                    .to_owned(),
-            })
+fn is_http_proxy(entry: &Entry, subsection: Option<&str>) -> bool {
+    entry.section == SECTION_HTTP
+        && entry.subsection.as_deref() == subsection
+        && entry.key.eq_ignore_ascii_case(KEY_PROXY)
+}
+
+fn is_git_proxy(entry: &Entry) -> bool {
+    entry.section == SECTION_CORE && entry.key.eq_ignore_ascii_case(KEY_GIT_PROXY)
+}
+
+fn proxy_of_entry(scope: Scope, path: &Path, entry: &Entry) -> Option<Proxy> {
+    if is_git_proxy(entry) {
+        let (command, host) = split_git_proxy(&entry.value);
+        let key = format!("{}.{}", SECTION_CORE, KEY_GIT_PROXY);
+        let mut proxy = Proxy::new(scope, Some(path), &key, read_script_proxy(command));
+        proxy.subsection = Some(format!("{}{}", GIT_SCHEME, host.unwrap_or_default()));
+        return Some(proxy);
+    }
+    if is_http_proxy(entry, entry.subsection.as_deref()) {
+        let key = match &entry.subsection {
+            Some(sub) => format!("{}.\"{}\".{}", SECTION_HTTP, sub, KEY_PROXY),
+            None => format!("{}.{}", SECTION_HTTP, KEY_PROXY),
+        };
+        let mut proxy = Proxy::new(scope, Some(path), &key, entry.value.clone());
+        proxy.subsection = entry.subsection.clone();
+        return Some(proxy);
+    }
+    None
+}
+
+// file of the last value of the http proxy in the scope, which may be an included one, so that a
+// new value is not overridden by the old one. It's the file written by default if none exists.
+fn get_http_proxy_file(scope: Scope, subsection: Option<&str>) -> Result<PathBuf, LaneError> {
+    let (entries, _) = read_scope(scope)?;
+    match entries.iter().rev().find(|(_, entry)| is_http_proxy(entry, subsection)) {
+        Some((path, _)) => Ok(path.clone()),
+        None => get_config_file_path(scope),
+    }
+}
+
+fn set_http_proxy(scope: Scope, subsection: Option<&str>, proxy: &str) -> Result<(), LaneError> {
+    let path = get_http_proxy_file(scope, subsection)?;
+    let mut config = read_config(&path)?;
+    config.set(SECTION_HTTP, subsection, KEY_PROXY, proxy);
+    write_config_file(&path, &config.to_string())
+}
+
+// remove proxies of matching http subsections from all files of the scope, including included ones,
+// as both xdg config and ~/.gitconfig are read
+fn unset_proxies(scope: Scope, matches: impl Fn(Option<&str>) -> bool) -> Result<(), LaneError> {
+    let mut result = Err(LaneError::NothingToDo());
+    for path in read_scope(scope)?.1 {
+        let mut config = read_config(&path)?;
+        let mut subsections: Vec<Option<String>> = config
+            .entries()
+            .into_iter()
+            .filter(|e| e.section == SECTION_HTTP && e.key.eq_ignore_ascii_case(KEY_PROXY))
+            .map(|e| e.subsection)
+            .filter(|subsection| matches(subsection.as_deref()))
+            .collect();
+        subsections.dedup();
+        if subsections.is_empty() {
+            continue;
         }
+        for subsection in subsections {
+            config.unset(SECTION_HTTP, subsection.as_deref(), KEY_PROXY);
+        }
+        write_config_file(&path, &config.to_string())?;
+        result = Ok(());
+    }
+    result
+}
+
+// proxies of ssh protocol are set in ssh config, which has no project scope
+fn get_ssh_config_path(scope: Scope) -> Result<PathBuf, LaneError> {
+    match scope {
+        Scope::Project => Err(make_unsupported_scope_error("ssh", scope)),
+        Scope::User => Ok(home_dir().ok_or(LaneError::NoHomeDir())?.join(SSH_CONFIG)),
+        Scope::System => Ok(PathBuf::from(SSH_SYSTEM_CONFIG)),
     }
-    Ok(result)
 }
 
-pub fn set_proxy(proxy: &str) -> Result<(), LaneError> {
-    validate_proxy_url(proxy)?;
-    exec("git", ["config", "--global", "http.proxy", proxy])
-        .then(|| ())
-        .ok_or_else(|| make_failure_error("Failed to execute 'git config' command"))
+// host of a ssh url, like "ssh://git@github.com:22/" or scp-like "git@github.com:"
+fn get_ssh_host(url: &str) -> Option<&str> {
+    let rest = match url.strip_prefix(SSH_SCHEME) {
+        Some(rest) => rest.split(['/', ':']).next()?,
+        None if !url.contains("://") && url.contains('@') => url.split(':').next()?,
+        None => return None,
+    };
+    let host = rest.rsplit('@').next()?;
+    (!host.is_empty()).then_some(host)
 }
 
-pub fn unset_proxy() -> Result<(), LaneError> {
-    exec("git", ["config", "--global", "--unset", "http.proxy"])
-        .then(|| ())
-        .ok_or_else(|| make_failure_error("Failed to execute 'git config' command"))
+fn read_ssh_config(path: &Path) -> Result<SshConfig, LaneError> {
+    let content = match path.exists() {
+        true => read_file(path).map_err(|_| make_invalid_file_error(path))?,
+        false => String::new(),
+    };
+    SshConfig::parse(&content).ok_or_else(|| make_invalid_file_error(path))
+}
+
+fn read_ssh_proxies(scope: Scope, result: &mut Vec<Proxy>) -> Result<(), LaneError> {
+    let path = get_ssh_config_path(scope)?;
+    for host in read_ssh_config(&path)?.hosts() {
+        let proxy = sshconfig::proxy_of_option(&host.key, &host.value);
+        let mut proxy = Proxy::new(scope, Some(&path), &host.key, proxy);
+        proxy.subsection = Some(format!("{}{}", SSH_SCHEME, host.host));
+        result.push(proxy);
+    }
+    Ok(())
+}
+
+fn set_ssh_proxy(scope: Scope, host: &str, proxy: &str) -> Result<(), LaneError> {
+    let (key, value) = sshconfig::proxy_option(proxy)
+        .ok_or_else(|| LaneError::InvalidProxyUrl(proxy.to_string()))?;
+    let path = get_ssh_config_path(scope)?;
+    let mut config = read_ssh_config(&path)?;
+    config.set(host, key, &value);
+    write_config_file(&path, &config.to_string())
+}
+
+// unset proxy of the host, or all hosts set by lane when `host` is `None`
+fn unset_ssh_proxies(scope: Scope, host: Option<&str>) -> Result<(), LaneError> {
+    let path = get_ssh_config_path(scope)?;
+    let mut config = read_ssh_config(&path)?;
+    let hosts: Vec<String> = match host {
+        Some(host) => vec![host.to_string()],
+        None => config.hosts().iter().map(|h| h.host.clone()).collect(),
+    };
+    let mut removed = false;
+    for host in hosts {
+        removed |= config.unset(&host);
+    }
+    match removed {
+        true => write_config_file(&path, &config.to_string()),
+        false => Err(LaneError::NothingToDo()),
+    }
+}
+
+// host of a git protocol url, `Some(None)` for "git://" meaning all hosts
+fn get_git_protocol_host(url: &str) -> Option<Option<&str>> {
+    let rest = url.strip_prefix(GIT_SCHEME)?;
+    let host = rest.split(['/', ':']).next().unwrap_or_default();
+    Some((!host.is_empty()).then_some(host))
+}
+
+// core.gitProxy is "<command>" for all hosts, or "<command> for <domain>"
+fn split_git_proxy(value: &str) -> (&str, Option<&str>) {
+    match value.rsplit_once(" for ") {
+        Some((command, host)) => (command.trim(), Some(host.trim())),
+        None => (value.trim(), None),
+    }
+}
+
+// scripts are shared by all users in system scope
+fn get_script_dir(scope: Scope) -> Result<PathBuf, LaneError> {
+    if scope == Scope::System {
+        return Ok(PathBuf::from(SYSTEM_SCRIPT_DIR));
+    }
+    let data_home = match env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => home_dir().ok_or(LaneError::NoHomeDir())?.join(DEFAULT_DATA_HOME),
+    };
+    Ok(data_home.join(LANE_DATA))
+}
+
+// scripts are named after the config file referring to them, by FNV-1a hash of its path which is
+// stable across builds, unlike the hasher of std
+fn get_script_prefix(config: &Path) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in config.to_string_lossy().bytes() {
+        hash = (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3);
+    }
+    format!("{}{:016x}-", SCRIPT_PREFIX, hash)
+}
+
+// git runs core.gitProxy with host and port as arguments, so a script is needed to pass them to
+// the proxy command
+fn write_proxy_script(scope: Scope, config: &Path, proxy: &str) -> Result<PathBuf, LaneError> {
+    let (header, comment, exec, extension) = SCRIPT_TEMPLATE;
+    let (host, port) = match cfg!(target_os = "windows") {
+        true => ("%1", "%2"),
+        false => ("\"$1\"", "\"$2\""),
+    };
+    let command = sshconfig::proxy_command(proxy, host, port)
+        .ok_or_else(|| LaneError::InvalidProxyUrl(proxy.to_string()))?;
+    let name: String =
+        proxy.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '-' }).collect();
+    let path =
+        get_script_dir(scope)?.join(format!("{}{}{}", get_script_prefix(config), name, extension));
+    let content = format!("{}{}{}{}\n{}{}\n", header, comment, SCRIPT_MARKER, proxy, exec, command);
+    write_config_file(&path, &content)?;
+    make_executable(&path)?;
+    Ok(path)
+}
+
+// scripts written by lane in the dir of the scope
+fn list_proxy_scripts(scope: Scope) -> Vec<PathBuf> {
+    let dir = match get_script_dir(scope) {
+        Ok(dir) => dir,
+        Err(_) => return vec![],
+    };
+    let entries = std::fs::read_dir(dir).into_iter().flatten().flatten();
+    let is_script = |name: &str| name.starts_with(SCRIPT_PREFIX);
+    entries
+        .map(|entry| entry.path())
+        .filter(|p| p.file_name().is_some_and(|n| is_script(&n.to_string_lossy())))
+        .collect()
+}
+
+// remove scripts written by lane for the config file, which it doesn't refer to any more. Scripts
+// of other config files are kept, as the script dir is shared by repositories of the user.
+fn remove_unused_scripts(
+    path: &Path,
+    config: &GitConfig,
+    commands: &[&str],
+) -> Result<(), LaneError> {
+    let prefix = get_script_prefix(path);
+    let entries = config.entries();
+    let used: Vec<&str> = entries
+        .iter()
+        .filter(|entry| is_git_proxy(entry))
+        .map(|entry| split_git_proxy(&entry.value).0)
+        .collect();
+    for command in commands.iter().filter(|command| !used.contains(command)) {
+        let script = Path::new(command);
+        let name = script.file_name().unwrap_or_default().to_string_lossy();
+        if name.starts_with(&prefix)
+            && read_file(script).is_ok_and(|content| content.contains(SCRIPT_MARKER))
+        {
+            remove_config_file(script)?;
+        }
+    }
+    Ok(())
+}
+
+// proxy of a script written by lane, or the command as is
+fn read_script_proxy(command: &str) -> String {
+    let content = read_file(Path::new(command)).unwrap_or_default();
+    let proxy = content.lines().find_map(|line| line.split_once(SCRIPT_MARKER).map(|(_, p)| p));
+    proxy.unwrap_or(command).trim().to_string()
+}
+
+fn set_git_protocol_proxy(scope: Scope, host: Option<&str>, proxy: &str) -> Result<(), LaneError> {
+    // old values of the host may be in any file of the scope, removed before writing the script
+    // which may be the old one
+    match unset_git_protocol_proxies(scope, |h| h == host) {
+        Ok(()) | Err(LaneError::NothingToDo()) => {}
+        Err(e) => return Err(e),
+    }
+    let path = get_config_file_path(scope)?;
+    let script = write_proxy_script(scope, &path, proxy)?;
+    let script = script.to_string_lossy();
+    let value = match host {
+        Some(host) => format!("{} for {}", script, host),
+        None => script.to_string(),
+    };
+    let mut config = read_config(&path)?;
+    config.add(SECTION_CORE, None, KEY_GIT_PROXY, &value);
+    write_config_file(&path, &config.to_string())
+}
+
+// remove core.gitProxy values whose host (`None` for all hosts) matches, and their scripts
+fn unset_git_protocol_proxies(
+    scope: Scope,
+    matches: impl Fn(Option<&str>) -> bool,
+) -> Result<(), LaneError> {
+    let mut result = Err(LaneError::NothingToDo());
+    for path in read_scope(scope)?.1 {
+        let mut config = read_config(&path)?;
+        let entries = config.entries();
+        let removed: Vec<&str> = entries
+            .iter()
+            .filter(|entry| is_git_proxy(entry) && matches(split_git_proxy(&entry.value).1))
+            .map(|entry| split_git_proxy(&entry.value).0)
+            .collect();
+        if config
+            .unset_matching(SECTION_CORE, None, KEY_GIT_PROXY, |v| matches(split_git_proxy(v).1))
+        {
+            write_config_file(&path, &config.to_string())?;
+            remove_unused_scripts(&path, &config, &removed)?;
+            result = Ok(());
+        }
+    }
+    result
+}
+
+// succeeded if any succeeded and none failed
+fn merge_results(results: Vec<Result<(), LaneError>>) -> Result<(), LaneError> {
+    let mut merged = Err(LaneError::NothingToDo());
+    for result in results {
+        match result {
+            Err(LaneError::NothingToDo()) => {}
+            Err(e) => return Err(e),
+            Ok(()) => merged = Ok(()),
+        }
+    }
+    merged
+}
+
+pub struct Git;
+
+impl App for Git {
+    fn name(&self) -> &'static str {
+        "git"
+    }
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::Proxy, Capability::UrlProxy]
+    }
+
+    fn scopes(&self) -> &'static [Scope] {
+        &[Scope::Project, Scope::User, Scope::System]
+    }
+
+    fn config_files(&self, scope: Scope) -> Vec<PathBuf> {
+        let mut files = get_config_files(scope).unwrap_or_default();
+        files.extend(read_scope(scope).map(|(_, included)| included).unwrap_or_default());
+        files.extend(get_config_file_path(scope));
+        files.extend(get_ssh_config_path(scope));
+        files.extend(list_proxy_scripts(scope));
+        files
+    }
+
+    fn get_proxies(&self, scope: Scope) -> Result<Vec<Proxy>, LaneError> {
+        let entries = match read_scope(scope) {
+            Ok((entries, _)) => entries,
+            Err(_) if scope == Scope::Project => return Ok(vec![]), // not in a repository
+            Err(e) => return Err(e),
+        };
+        let mut result: Vec<Proxy> =
+            entries.iter().filter_map(|(path, entry)| proxy_of_entry(scope, path, entry)).collect();
+        if scope != Scope::Project {
+            read_ssh_proxies(scope, &mut result)?;
+        }
+        Ok(result)
+    }
+
+    fn set_proxy(&self, scope: Scope, proxy: &str) -> Result<(), LaneError> {
+        validate_proxy_url(proxy)?;
+        set_http_proxy(scope, None, proxy)
+    }
+
+    fn unset_proxy(&self, scope: Scope) -> Result<(), LaneError> {
+        unset_proxies(scope, |subsection| subsection.is_none())
+    }
+
+    // urls of ssh protocol are proxied in ssh config, and git protocol by core.gitProxy
+    fn set_url_proxy(&self, scope: Scope, url: &str, proxy: &str) -> Result<(), LaneError> {
+        validate_proxy_url(proxy)?;
+        if let Some(host) = get_ssh_host(url) {
+            return set_ssh_proxy(scope, host, proxy);
+        }
+        if let Some(host) = get_git_protocol_host(url) {
+            return set_git_protocol_proxy(scope, host, proxy);
+        }
+        set_http_proxy(scope, Some(url), proxy)
+    }
+
+    fn unset_url_proxy(&self, scope: Scope, url: Option<&str>) -> Result<(), LaneError> {
+        if let Some(host) = url.and_then(get_ssh_host) {
+            return unset_ssh_proxies(scope, Some(host));
+        }
+        if let Some(host) = url.and_then(get_git_protocol_host) {
+            return unset_git_protocol_proxies(scope, |h| h == host);
+        }
+        let result = unset_proxies(scope, |subsection| match url {
+            Some(url) => subsection == Some(url),
+            None => subsection.is_some(),
+        });
+        if url.is_some() {
+            return result;
+        }
+        // all urls, including those of git and ssh protocols
+        let mut results = vec![result, unset_git_protocol_proxies(scope, |_| true)];
+        if scope != Scope::Project {
+            results.push(unset_ssh_proxies(scope, None));
+        }
+        merge_results(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        glob_match(&pattern.chars().collect::<Vec<_>>(), &text.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn find_ssh_host() {
+        assert_eq!(get_ssh_host("ssh://git@github.com:22/a.git"), Some("github.com"));
+        assert_eq!(get_ssh_host("ssh://github.com"), Some("github.com"));
+        assert_eq!(get_ssh_host("git@github.com:a/b.git"), Some("github.com"));
+        assert_eq!(get_ssh_host("git@github.com"), Some("github.com"));
+        assert_eq!(get_ssh_host("https://github.com"), None);
+    }
+
+    #[test]
+    fn parse_git_protocol_proxy() {
+        assert_eq!(get_git_protocol_host("git://github.com/a.git"), Some(Some("github.com")));
+        assert_eq!(get_git_protocol_host("git://"), Some(None));
+        assert_eq!(get_git_protocol_host("https://github.com"), None);
+        assert_eq!(
+            split_git_proxy("/bin/proxy for kernel.org"),
+            ("/bin/proxy", Some("kernel.org"))
+        );
+        assert_eq!(split_git_proxy("/bin/proxy"), ("/bin/proxy", None));
+    }
+
+    #[test]
+    fn read_included_entries() {
+        let dir = env::temp_dir().join(format!("lane-test-git-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (config, included) = (dir.join("config"), dir.join("proxy.inc"));
+        let content = "[http]\n\tproxy = http://a:1\n[include]\n\tpath = proxy.inc\n[include]\n\tpath = none\n";
+        std::fs::write(&config, content).unwrap();
+        std::fs::write(&included, "[http]\n\tproxy = http://b:2\n").unwrap();
+
+        let (mut entries, mut files) = (vec![], vec![]);
+        read_entries(&config, 0, &mut entries, &mut files).unwrap();
+        assert_eq!(files, [config.clone(), included.clone()]);
+        let proxies: Vec<(&PathBuf, &str)> = entries
+            .iter()
+            .filter(|(_, entry)| is_http_proxy(entry, None))
+            .map(|(path, entry)| (path, entry.value.as_str()))
+            .collect();
+        // the included value overrides the former one
+        assert_eq!(proxies, [(&config, "http://a:1"), (&included, "http://b:2")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn remove_scripts_of_config_only() {
+        let dir = env::temp_dir().join(format!("lane-test-git-scripts-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (config_path, other_path) = (dir.join("a/.git/config"), dir.join("b/.git/config"));
+        assert_ne!(get_script_prefix(&config_path), get_script_prefix(&other_path));
+        let script = |config: &Path| {
+            let path = dir.join(format!("{}socks5", get_script_prefix(config)));
+            std::fs::write(&path, format!("#!/bin/sh\n# {}socks5://a:1\n", SCRIPT_MARKER)).unwrap();
+            path.to_string_lossy().to_string()
+        };
+        let (own, other) = (script(&config_path), script(&other_path));
+
+        // still referred to by the config
+        let config = GitConfig::parse(&format!("[core]\n\tgitProxy = {} for a.org\n", own));
+        remove_unused_scripts(&config_path, &config, &[&own, &other]).unwrap();
+        assert!(Path::new(&own).exists());
+        // the script of another config is kept, even if it's not referred to here
+        remove_unused_scripts(&config_path, &GitConfig::parse(""), &[&own, &other]).unwrap();
+        assert!(!Path::new(&own).exists());
+        assert!(Path::new(&other).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn match_include_patterns() {
+        assert!(matches("/home/me/work/**", "/home/me/work/lane/.git"));
+        assert!(matches("**/lane/**", "/home/me/lane/.git"));
+        assert!(matches("**/.git", ".git"));
+        assert!(matches("feature/*", "feature/a"));
+        assert!(!matches("feature/*", "feature/a/b"));
+        assert!(matches("v?", "v1"));
+        assert!(!matches("/home/me/work/**", "/home/me/other/.git"));
+    }
 }
@@ -0,0 +1,482 @@
+// A format preserving editor of git config files: https://git-scm.com/docs/git-config#_syntax
+//
+// Lines not modified are kept as is, including comments, indentation and line endings. New keys are
+// added to the last section with the same name, as `git config` does.
+
+use std::fmt::Display;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Section {
+    /// Lowercase name of the section
+    name: String,
+    subsection: Option<String>,
+}
+
+impl Section {
+    fn matches(&self, name: &str, subsection: Option<&str>) -> bool {
+        self.name.eq_ignore_ascii_case(name) && self.subsection.as_deref() == subsection
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Line {
+    /// Text of the line, several physical lines for a value continued with backslash
+    text: String,
+    /// Section header starting the line, and length of the header in `text`
+    header: Option<(Section, usize)>,
+    /// Key and value of the line, after the header if any
+    entry: Option<(String, String)>,
+}
+
+/// A value in git config
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// Lowercase name of the section
+    pub section: String,
+    pub subsection: Option<String>,
+    /// Key as written in the file, keys are case insensitive
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct GitConfig {
+    lines: Vec<Line>,
+}
+
+// parse "[section]", "[section "subsection"]" or legacy "[section.subsection]", returning the
+// section and length of the header
+fn parse_header(line: &str) -> Option<(Section, usize)> {
+    let start = line.len() - line.trim_start().len();
+    let rest = line[start..].strip_prefix('[')?;
+    let name_len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '.'))?;
+    let name = &rest[..name_len];
+    let rest = &rest[name_len..];
+    if let Some(rest) = rest.strip_prefix(']') {
+        let header_len = line.len() - rest.len();
+        let section = match name.split_once('.') {
+            Some((name, sub)) => Section {
+                name: name.to_ascii_lowercase(),
+                subsection: Some(sub.to_ascii_lowercase()),
+            },
+            None => Section { name: name.to_ascii_lowercase(), subsection: None },
+        };
+        return Some((section, header_len));
+    }
+    // quoted subsection, where only \" and \\ are escaped
+    let quoted = rest.trim_start_matches([' ', '\t']).strip_prefix('"')?;
+    let mut chars = quoted.char_indices();
+    let mut subsection = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => subsection.push(chars.next()?.1),
+            '"' => {
+                let after = quoted[i + 1..].strip_prefix(']')?;
+                let section =
+                    Section { name: name.to_ascii_lowercase(), subsection: Some(subsection) };
+                return Some((section, line.len() - after.len()));
+            }
+            _ => subsection.push(c),
+        }
+    }
+    None
+}
+
+// parse a value, `None` when it's continued by a trailing backslash
+fn parse_value(raw: &str) -> Option<String> {
+    let mut value = String::new();
+    let mut spaces = String::new();
+    let mut quoted = false;
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let escaped = match chars.next() {
+                    None => return None,
+                    Some('\n') => continue,
+                    Some('\r') if chars.clone().next() == Some('\n') => {
+                        chars.next();
+                        continue;
+                    }
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('b') => '\u{8}',
+                    Some(other) => other,
+                };
+                value.push_str(&spaces);
+                spaces.clear();
+                value.push(escaped);
+            }
+            '"' => {
+                value.push_str(&spaces);
+                spaces.clear();
+                quoted = !quoted;
+            }
+            ';' | '#' if !quoted => break,
+            ' ' | '\t' | '\r' | '\n' if !quoted => {
+                // leading spaces are dropped, and trailing ones when no content follows
+                if !value.is_empty() {
+                    spaces.push(c);
+                }
+            }
+            _ => {
+                value.push_str(&spaces);
+                spaces.clear();
+                value.push(c);
+            }
+        }
+    }
+    Some(value)
+}
+
+// parse "key = value" or "key", returning the key and the raw value part if any
+fn parse_key(text: &str) -> Option<(&str, Option<&str>)> {
+    let text = text.trim_start();
+    if !text.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let key_len =
+        text.find(|c: char| !(c.is_ascii_alphanumeric() || c == '-')).unwrap_or(text.len());
+    let (key, rest) = text.split_at(key_len);
+    let rest = rest.trim_start_matches([' ', '\t']);
+    match rest.chars().next() {
+        Some('=') => Some((key, Some(&rest[1..]))),
+        None | Some('\r') | Some(';') | Some('#') => Some((key, None)),
+        _ => None,
+    }
+}
+
+fn format_value(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+    let needs_quotes = value.starts_with(' ') || value.ends_with(' ') || value.contains([';', '#']);
+    match needs_quotes {
+        true => format!("\"{}\"", escaped),
+        false => escaped,
+    }
+}
+
+fn format_header(name: &str, subsection: Option<&str>) -> String {
+    match subsection {
+        Some(sub) => format!("[{} \"{}\"]", name, sub.replace('\\', "\\\\").replace('"', "\\\"")),
+        None => format!("[{}]", name),
+    }
+}
+
+fn format_entry(key: &str, value: &str) -> String {
+    format!("{} = {}", key, format_value(value))
+}
+
+impl GitConfig {
+    pub fn parse(content: &str) -> Self {
+        let mut lines = vec![];
+        let mut physical = content.split('\n');
+        while let Some(line) = physical.next() {
+            let mut text = line.to_string();
+            let header = parse_header(line);
+            let rest = match &header {
+                Some((_, len)) => &line[*len..],
+                None => line,
+            };
+            let entry = match parse_key(rest) {
+                Some((key, None)) => Some((key.to_string(), "true".to_string())),
+                Some((key, Some(raw))) => {
+                    let key = key.to_string();
+                    let mut raw = raw.to_string();
+                    let value = loop {
+                        match (parse_value(&raw), physical.clone().next()) {
+                            (Some(value), _) => break value,
+                            (None, Some(next)) => {
+                                physical.next();
+                                raw = format!("{}\n{}", raw, next);
+                                text = format!("{}\n{}", text, next);
+                            }
+                            (None, None) => {
+                                break parse_value(&raw[..raw.len() - 1]).unwrap_or_default()
+                            }
+                        }
+                    };
+                    Some((key, value))
+                }
+                None => None,
+            };
+            lines.push(Line { text, header, entry });
+        }
+        GitConfig { lines }
+    }
+
+    // index of the section header in effect for each line
+    fn line_sections(&self) -> Vec<Option<usize>> {
+        let mut current = None;
+        self.lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                if line.header.is_some() {
+                    current = Some(i);
+                }
+                current
+            })
+            .collect()
+    }
+
+    fn section_of(&self, header: Option<usize>) -> Option<&Section> {
+        header.and_then(|i| self.lines[i].header.as_ref()).map(|(section, _)| section)
+    }
+
+    /// All values in the order of the file
+    pub fn entries(&self) -> Vec<Entry> {
+        let sections = self.line_sections();
+        self.lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| {
+                let section = self.section_of(sections[i])?;
+                let (key, value) = line.entry.as_ref()?;
+                Some(Entry {
+                    section: section.name.clone(),
+                    subsection: section.subsection.clone(),
+                    key: key.clone(),
+                    value: value.clone(),
+                })
+            })
+            .collect()
+    }
+
+    // lines with the key in matching sections
+    fn find(&self, name: &str, subsection: Option<&str>, key: &str) -> Vec<usize> {
+        let sections = self.line_sections();
+        (0..self.lines.len())
+            .filter(|i| self.section_of(sections[*i]).is_some_and(|s| s.matches(name, subsection)))
+            .filter(|i| {
+                self.lines[*i].entry.as_ref().is_some_and(|(k, _)| k.eq_ignore_ascii_case(key))
+            })
+            .collect()
+    }
+
+    fn replace_entry(&mut self, index: usize, value: &str) {
+        let line = &mut self.lines[index];
+        let key = line.entry.as_ref().map(|(key, _)| key.clone()).unwrap_or_default();
+        line.text = match &line.header {
+            // split the entry after a header to its own line
+            Some((_, len)) => format!("{}\n\t{}", &line.text[..*len], format_entry(&key, value)),
+            None => {
+                let indent = &line.text[..line.text.len() - line.text.trim_start().len()];
+                format!("{}{}", indent, format_entry(&key, value))
+            }
+        };
+        line.entry = Some((key, value.to_string()));
+    }
+
+    fn insert_line(&mut self, index: usize, text: String) {
+        let header = parse_header(&text);
+        let entry = match header {
+            Some(_) => None,
+            None => parse_key(&text)
+                .map(|(key, raw)| (key.to_string(), raw.and_then(parse_value).unwrap_or_default())),
+        };
+        self.lines.insert(index, Line { text, header, entry });
+    }
+
+    /// Set the value of the key, replacing the last one if existed, or adding it to the last matching
+    /// section (or a new section at the end)
+    pub fn set(&mut self, name: &str, subsection: Option<&str>, key: &str, value: &str) {
+        if let Some(index) = self.find(name, subsection, key).last() {
+            return self.replace_entry(*index, value);
+        }
+        self.add(name, subsection, key, value);
+    }
+
+    /// Add a value of the key, keeping existed ones, for keys with multiple values
+    pub fn add(&mut self, name: &str, subsection: Option<&str>, key: &str, value: &str) {
+        let sections = self.line_sections();
+        let last_header = (0..self.lines.len()).rev().find(|i| {
+            let header = self.lines[*i].header.as_ref();
+            header.is_some_and(|(section, _)| section.matches(name, subsection))
+        });
+        let line = format!("\t{}", format_entry(key, value));
+        match last_header {
+            Some(header) => {
+                // after the last entry of the section, so comments and blank lines before the next
+                // section stay there
+                let index = (header..self.lines.len())
+                    .take_while(|i| *i == header || self.lines[*i].header.is_none())
+                    .filter(|i| sections[*i] == Some(header) && self.lines[*i].entry.is_some())
+                    .last()
+                    .unwrap_or(header);
+                self.insert_line(index + 1, line);
+            }
+            None => {
+                // keep the trailing newline at the end
+                let mut index = self.lines.len();
+                if self.lines.last().is_some_and(|l| l.text.is_empty()) {
+                    index -= 1;
+                }
+                if index == self.lines.len() {
+                    self.insert_line(index, String::new());
+                }
+                self.insert_line(index, line);
+                self.insert_line(index, format_header(name, subsection));
+            }
+        }
+    }
+
+    fn remove_entries(&mut self, mut indexes: Vec<usize>) {
+        let sections = self.line_sections();
+        let mut headers: Vec<usize> = indexes.iter().filter_map(|i| sections[*i]).collect();
+        headers.dedup();
+        indexes.sort();
+        for index in indexes.into_iter().rev() {
+            let line = &mut self.lines[index];
+            match &line.header {
+                Some((_, len)) => {
+                    line.text.truncate(*len);
+                    line.entry = None;
+                }
+                None => {
+                    self.lines.remove(index);
+                    // update indexes of headers after the removed line
+                    headers.iter_mut().filter(|h| **h > index).for_each(|h| *h -= 1);
+                }
+            }
+        }
+        // remove sections left empty, i.e. without entries or comments
+        for header in headers.into_iter().rev() {
+            let end = (header + 1..self.lines.len())
+                .find(|i| self.lines[*i].header.is_some())
+                .unwrap_or(self.lines.len());
+            let header_line = &self.lines[header].text;
+            let header_len = self.lines[header].header.as_ref().map(|(_, len)| *len).unwrap_or(0);
+            let empty = header_line[header_len..].trim().is_empty()
+                && self.lines[header + 1..end].iter().all(|l| l.text.trim().is_empty());
+            if empty {
+                self.lines.remove(header);
+            }
+        }
+    }
+
+    /// Remove all values of the key, returns whether any value is removed
+    pub fn unset(&mut self, name: &str, subsection: Option<&str>, key: &str) -> bool {
+        self.unset_matching(name, subsection, key, |_| true)
+    }
+
+    /// Remove values of the key matching the predicate, returns whether any value is removed
+    pub fn unset_matching(
+        &mut self,
+        name: &str,
+        subsection: Option<&str>,
+        key: &str,
+        predicate: impl Fn(&str) -> bool,
+    ) -> bool {
+        let indexes: Vec<usize> = self
+            .find(name, subsection, key)
+            .into_iter()
+            .filter(|i| self.lines[*i].entry.as_ref().is_some_and(|(_, value)| predicate(value)))
+            .collect();
+        let removed = !indexes.is_empty();
+        self.remove_entries(indexes);
+        removed
+    }
+}
+
+impl Display for GitConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let texts: Vec<&str> = self.lines.iter().map(|line| line.text.as_str()).collect();
+        write!(f, "{}", texts.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the value in effect, i.e. the last one
+    fn get<'a>(config: &'a GitConfig, name: &str, sub: Option<&str>, key: &str) -> Option<&'a str> {
+        let index = *config.find(name, sub, key).last()?;
+        config.lines[index].entry.as_ref().map(|(_, value)| value.as_str())
+    }
+
+    const CONFIG: &str = r#"# user config
+[user]
+	name = Someone ; comment
+	email = "someone@example.com"
+[http "https://example.com/"]
+	proxy = http://127.0.0.1:1080
+[Core]
+	editor = vim \
+  -n
+[http]
+	sslVerify
+"#;
+
+    #[test]
+    fn parse_and_keep_format() {
+        let config = GitConfig::parse(CONFIG);
+        assert_eq!(config.to_string(), CONFIG);
+        assert_eq!(get(&config, "user", None, "name"), Some("Someone"));
+        assert_eq!(get(&config, "user", None, "EMAIL"), Some("someone@example.com"));
+        assert_eq!(
+            get(&config, "http", Some("https://example.com/"), "proxy"),
+            Some("http://127.0.0.1:1080")
+        );
+        assert_eq!(get(&config, "core", None, "editor"), Some("vim   -n"));
+        assert_eq!(get(&config, "http", None, "sslverify"), Some("true"));
+        assert_eq!(get(&config, "http", None, "proxy"), None);
+    }
+
+    #[test]
+    fn parse_legacy_and_inline_header() {
+        let config =
+            GitConfig::parse("[http.Example] proxy = a # c\n[http \"a\\\"b\"]\nproxy=\"b;\"\n");
+        assert_eq!(get(&config, "http", Some("example"), "proxy"), Some("a"));
+        assert_eq!(get(&config, "http", Some("a\"b"), "proxy"), Some("b;"));
+    }
+
+    #[test]
+    fn set_value() {
+        let mut config = GitConfig::parse(CONFIG);
+        config.set("http", None, "proxy", "http://localhost:8080");
+        config.set("http", Some("https://example.com/"), "proxy", "socks5://localhost:1080");
+        let expected = CONFIG
+            .replace("http://127.0.0.1:1080", "socks5://localhost:1080")
+            .replace("\tsslVerify\n", "\tsslVerify\n\tproxy = http://localhost:8080\n");
+        assert_eq!(config.to_string(), expected);
+    }
+
+    #[test]
+    fn set_value_to_new_section() {
+        let mut config = GitConfig::parse("[user]\n\tname = a");
+        config.set("http", Some("https://a\"b/"), "proxy", "http://localhost:8080");
+        assert_eq!(
+            config.to_string(),
+            "[user]\n\tname = a\n[http \"https://a\\\"b/\"]\n\tproxy = http://localhost:8080\n"
+        );
+        let mut config = GitConfig::parse("");
+        config.set("http", None, "proxy", "p;1");
+        assert_eq!(config.to_string(), "[http]\n\tproxy = \"p;1\"\n");
+    }
+
+    #[test]
+    fn unset_value() {
+        let mut config = GitConfig::parse(CONFIG);
+        assert!(config.unset("http", Some("https://example.com/"), "proxy"));
+        assert!(!config.unset("http", Some("https://example.com/"), "proxy"));
+        assert!(config.unset("core", None, "editor"));
+        let expected = CONFIG
+            .replace("[http \"https://example.com/\"]\n\tproxy = http://127.0.0.1:1080\n", "")
+            .replace("[Core]\n\teditor = vim \\\n  -n\n", "");
+        assert_eq!(config.to_string(), expected);
+
+        let mut config = GitConfig::parse("[http] proxy = a\n\t# keep\n");
+        assert!(config.unset("http", None, "proxy"));
+        assert_eq!(config.to_string(), "[http]\n\t# keep\n");
+    }
+}
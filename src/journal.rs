@@ -0,0 +1,303 @@
+// Journal of config files changed by lane, for undo. It's kept in
+// $XDG_STATE_HOME/lane/journal.jsonl (~/.local/state/lane/journal.jsonl by default), one entry per
+// line, oldest first.
+
+use crate::dry_run;
+use crate::error::*;
+use crate::utils::*;
+use dirs::home_dir;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fs::{create_dir_all, metadata, read_to_string, remove_file, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const LANE_STATE: &str = "lane";
+const JOURNAL: &str = "journal.jsonl";
+const LOCK: &str = "lock";
+const DEFAULT_STATE_HOME: &str = ".local/state";
+// older entries are dropped
+const MAX_ENTRIES: usize = 100;
+
+/// Content of a config file before and after a change
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: PathBuf,
+    /// `None` when the file didn't exist
+    pub before: Option<String>,
+    /// `None` when the file was removed
+    pub after: Option<String>,
+    /// Whether the file was executable before, like proxy scripts of git
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub executable: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// Changes made by a single lane command
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Seconds since unix epoch
+    pub timestamp: u64,
+    pub command: String,
+    pub changes: Vec<FileChange>,
+}
+
+fn get_state_dir() -> Result<PathBuf, LaneError> {
+    let state_home = match env::var_os("XDG_STATE_HOME").filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => home_dir().ok_or(LaneError::NoHomeDir())?.join(DEFAULT_STATE_HOME),
+    };
+    Ok(state_home.join(LANE_STATE))
+}
+
+/// Take the advisory lock of lane state, so config changes of concurrent lane commands don't
+/// clobber each other. It's released when the returned file is dropped. Failing to lock is only
+/// warned, as configs could be changed without it.
+pub(crate) fn lock() -> Option<File> {
+    if dry_run::is_active() {
+        return None;
+    }
+    let result = get_state_dir().and_then(|dir| {
+        let path = dir.join(LOCK);
+        create_dir_all(&dir).map_err(|e| make_write_file_error(&path, &e))?;
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| make_write_file_error(&path, &e))?;
+        file.lock().map_err(|e| make_write_file_error(&path, &e))?;
+        Ok(file)
+    });
+    match result {
+        Ok(file) => Some(file),
+        Err(e) => {
+            warn!("Failed to lock lane state: {}", e);
+            None
+        }
+    }
+}
+
+fn read_content(path: &Path) -> Option<String> {
+    read_to_string(path).ok()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    false
+}
+
+/// Content of config files before running a command
+pub struct Snapshot {
+    files: Vec<(PathBuf, Option<String>, bool)>,
+}
+
+impl Snapshot {
+    pub fn take(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        let mut paths: Vec<PathBuf> = paths.into_iter().collect();
+        paths.sort();
+        paths.dedup();
+        let files = paths
+            .into_iter()
+            .map(|path| (path.clone(), read_content(&path), is_executable(&path)))
+            .collect();
+        Snapshot { files }
+    }
+
+    /// Add files listed again after running a command but not in the snapshot, as files created
+    /// by it, like scripts named after the proxy set
+    pub fn add_created(&mut self, paths: impl IntoIterator<Item = PathBuf>) {
+        for path in paths {
+            if !self.files.iter().any(|(p, ..)| *p == path) {
+                self.files.push((path, None, false));
+            }
+        }
+    }
+
+    /// Files changed since the snapshot was taken
+    pub fn changes(&self) -> Vec<FileChange> {
+        self.files
+            .iter()
+            .map(|(path, before, executable)| FileChange {
+                path: path.clone(),
+                before: before.clone(),
+                after: read_content(path),
+                executable: *executable,
+            })
+            .filter(|change| change.before != change.after)
+            .collect()
+    }
+}
+
+/// Journal kept in a state dir
+pub struct Journal {
+    dir: PathBuf,
+}
+
+impl Journal {
+    pub fn at(dir: impl Into<PathBuf>) -> Self {
+        Journal { dir: dir.into() }
+    }
+
+    /// Entries of the journal, oldest first. A missing journal is the same as an empty one.
+    pub fn load(&self) -> Result<Vec<Entry>, LaneError> {
+        let path = self.dir.join(JOURNAL);
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        let content = read_to_string(&path).map_err(|_| make_invalid_file_error(&path))?;
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(|_| make_invalid_file_error(&path)))
+            .collect()
+    }
+
+    fn save(&self, entries: &[Entry]) -> Result<(), LaneError> {
+        let path = self.dir.join(JOURNAL);
+        create_dir_all(&self.dir).map_err(|e| make_write_file_error(&path, &e))?;
+        let mut content = String::new();
+        for entry in entries.iter().skip(entries.len().saturating_sub(MAX_ENTRIES)) {
+            content += &serde_json::to_string(entry).map_err(make_failure_error)?;
+            content.push('\n');
+        }
+        // config files may contain credentials, so the journal is readable by the owner only
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&path).map_err(|e| make_write_file_error(&path, &e))?;
+        file.write_all(content.as_bytes()).map_err(|e| make_write_file_error(&path, &e))
+    }
+
+    /// Add the changes to the journal as an entry of current command
+    pub fn record(&self, changes: Vec<FileChange>) -> Result<(), LaneError> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let entry = Entry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            command: env::args().collect::<Vec<_>>().join(" "),
+            changes,
+        };
+        let mut entries = self.load()?;
+        entries.push(entry);
+        self.save(&entries)
+    }
+
+    /// Revert the latest entry of the journal and remove it. Files modified by others after the
+    /// change are not touched unless `force` is set.
+    pub fn undo(&self, force: bool) -> Result<Entry, LaneError> {
+        let mut entries = self.load()?;
+        let entry = entries.pop().ok_or(LaneError::NothingToDo())?;
+        if !force {
+            if let Some(change) = entry.changes.iter().find(|c| read_content(&c.path) != c.after) {
+                return Err(make_failure_error(format!(
+                    "{} was modified after the change, use --force to undo anyway",
+                    change.path.display()
+                )));
+            }
+        }
+        for change in entry.changes.iter().rev() {
+            restore(change)?;
+        }
+        self.save(&entries)?;
+        Ok(entry)
+    }
+}
+
+fn journal() -> Result<Journal, LaneError> {
+    Ok(Journal::at(get_state_dir()?))
+}
+
+/// Entries of the journal in lane state dir, oldest first
+pub fn load() -> Result<Vec<Entry>, LaneError> {
+    journal()?.load()
+}
+
+/// Add the changes to the journal in lane state dir. Failing to record is only warned, as the
+/// changes have been made already.
+pub fn record(changes: Vec<FileChange>) {
+    if let Err(e) = journal().and_then(|journal| journal.record(changes)) {
+        warn!("Failed to record changes for undo: {}", e);
+    }
+}
+
+/// Revert the latest entry of the journal in lane state dir, holding the lock of lane state
+pub fn undo(force: bool) -> Result<Entry, LaneError> {
+    let _lock = lock();
+    journal()?.undo(force)
+}
+
+fn restore(change: &FileChange) -> Result<(), LaneError> {
+    let path = &change.path;
+    match &change.before {
+        Some(content) => {
+            write_file(path, content).map_err(|e| make_write_file_error(path, &e))?;
+            match change.executable {
+                true => make_executable(path),
+                false => Ok(()),
+            }
+        }
+        None if path.exists() => remove_file(path).map_err(|e| make_write_file_error(path, &e)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    #[test]
+    fn snapshot_and_undo() {
+        let dir = env::temp_dir().join(format!("lane-test-journal-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        let journal = Journal::at(dir.join("state"));
+        let (modified, created, removed) =
+            (dir.join("config"), dir.join("new"), dir.join("script"));
+        write(&modified, "old\n").unwrap();
+        write(&removed, "#!/bin/sh\n").unwrap();
+        make_executable(&removed).unwrap();
+
+        let mut snapshot = Snapshot::take([modified.clone(), removed.clone()]);
+        write(&modified, "new\n").unwrap();
+        write(&created, "created\n").unwrap();
+        std::fs::remove_file(&removed).unwrap();
+        snapshot.add_created([created.clone(), modified.clone()]);
+        journal.record(snapshot.changes()).unwrap();
+        let entries = journal.load().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].changes.len(), 3);
+
+        // files modified after the change are kept unless forced
+        write(&modified, "edited\n").unwrap();
+        assert!(journal.undo(false).is_err());
+        let entry = journal.undo(true).unwrap();
+        assert_eq!(entry.changes.len(), 3);
+        assert_eq!(read_content(&modified).as_deref(), Some("old\n"));
+        assert!(!created.exists());
+        assert_eq!(read_content(&removed).as_deref(), Some("#!/bin/sh\n"));
+        #[cfg(unix)]
+        assert!(is_executable(&removed));
+        assert_eq!(journal.load().unwrap(), vec![]);
+        assert_eq!(journal.undo(false), Err(LaneError::NothingToDo()));
+        remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,41 @@
+//! lane - quickly show/set/clear proxies/mirrors for different programs
+//!
+//! Every supported program is an [`App`] registered in [`apps()`]. The functions in [`ops`] run an
+//! operation on all apps having the needed [`Capability`] (or a single one selected by name) and
+//! return one [`Outcome`] per app, e.g.
+//!
+//! ```no_run
+//! for outcome in lane::get_proxies(Some("git"), None) {
+//!     if let Ok(proxies) = outcome.result {
+//!         println!("{}: {} proxies", outcome.app, proxies.len());
+//!     }
+//! }
+//! ```
+
+pub mod app;
+pub mod bun;
+pub mod cargo;
+pub mod config;
+pub mod curl;
+pub mod dry_run;
+pub mod error;
+pub mod git;
+mod gitconfig;
+pub mod journal;
+pub mod npm;
+mod npmrc;
+pub mod ops;
+pub mod pip;
+mod pipconf;
+pub mod pnpm;
+mod sshconfig;
+mod utils;
+pub mod yarn;
+mod yarnrc;
+
+pub use app::{
+    app_names_with, apps, apps_with, find_app, mask_secret, App, Capability, Mirror, MirrorEntry,
+    Proxy, Scope,
+};
+pub use error::LaneError;
+pub use ops::*;
@@ -2,16 +2,12 @@ use crate::args::Cli;
 use clap::Parser;
 
 mod args;
-mod cargo;
-mod curl;
-mod error;
-mod git;
 mod manager;
-mod npm;
-mod utils;
+mod output;
 
 fn main() {
-    env_logger::init();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
     let cli = Cli::parse();
-    manager::handle_cli_args(cli);
+    let status = manager::handle_cli_args(cli);
+    std::process::exit(status as i32);
 }
@@ -1,9 +1,10 @@
 use crate::args::*;
-use crate::cargo;
-use crate::curl;
-use crate::git;
-use crate::npm;
-use std::fmt::Display;
+use crate::output::*;
+use lane::config::{self, Config, Profile};
+use lane::dry_run::{self, Change};
+use lane::journal::{self, Entry};
+use lane::{find_app, LaneError, Outcome, Scope, Summary};
+use std::{env, fmt::Display};
 
 const CLEAR_PROXY: &str = "Clear proxy";
 const SET_PROXY: &str = "Set proxy";
@@ -12,16 +13,12 @@ const CLEAR_MIRROR: &str = "Clear mirror";
 const GET_MIRROR: &str = "Get mirror";
 const SET_MIRROR: &str = "Set mirror";
 const PROMPT_NO_MIRROR: &str = "No mirror is set!";
-
-fn print_proxy_option<D>(app: D, proxy_option: &Option<impl Display>)
-where
-    D: AsRef<str>,
-{
-    match proxy_option {
-        None => println!("{}: {}", app.as_ref(), PROMPT_NO_PROXY),
-        Some(proxy) => println!("{}: {}", app.as_ref(), proxy),
-    }
-}
+const USE_PROFILE: &str = "Use profile";
+const ADD_PROFILE: &str = "Add profile";
+const REMOVE_PROFILE: &str = "Remove profile";
+const PROMPT_NO_PROFILE: &str = "No profile is defined!";
+const UNDO: &str = "Undo";
+const PROMPT_NO_HISTORY: &str = "No change is recorded!";
 
 fn print_proxies_result<D>(app: D, proxies_result: &Result<Vec<impl Display>, impl Display>)
 where
@@ -45,13 +42,29 @@ where
 fn print_mirror_result<D1, D2, E>(app: D1, mirror_result: &Result<Option<D2>, E>)
 where
     D1: AsRef<str>,
-    D2: AsRef<str>,
+    D2: Display,
     E: Display,
 {
     match mirror_result {
         Err(e) => println!("{}: {} failed! Error: {}", app.as_ref(), GET_MIRROR, e),
         Ok(None) => println!("{}: {}", app.as_ref(), PROMPT_NO_MIRROR),
-        Ok(Some(mirror)) => println!("{}: Using mirror: {}", app.as_ref(), mirror.as_ref()),
+        Ok(Some(mirror)) => println!("{}: Using mirror: {}", app.as_ref(), mirror),
+    }
+}
+
+fn print_mirrors_result<D>(app: D, mirrors_result: &Result<Vec<impl Display>, impl Display>)
+where
+    D: AsRef<str>,
+{
+    match mirrors_result {
+        Err(e) => println!("{}: {} failed! Error: {}", app.as_ref(), GET_MIRROR, e),
+        Ok(mirrors) if mirrors.is_empty() => println!("{}: {}", app.as_ref(), PROMPT_NO_MIRROR),
+        Ok(mirrors) => {
+            println!("{}:", app.as_ref());
+            for mirror in mirrors {
+                println!("    {}", mirror);
+            }
+        }
     }
 }
 
@@ -65,79 +78,413 @@ where
     }
 }
 
-macro_rules! do_work {
-    ($args: ident, $app: ident, $func: expr, $printer: ident) => {
-        if $args.app == None || $args.app == Some($app) {
-            $printer($app, &$func());
+/// Process exit codes, 2 is left for command line usage errors reported by clap
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExitStatus {
+    Success = 0,
+    TotalFailure = 1,
+    PartialFailure = 3,
+    NothingToDo = 4,
+}
+
+impl From<Summary> for ExitStatus {
+    fn from(summary: Summary) -> Self {
+        match summary {
+            Summary { failed: 0, succeeded: 0, .. } => ExitStatus::NothingToDo,
+            Summary { failed: 0, .. } => ExitStatus::Success,
+            Summary { succeeded: 0, .. } => ExitStatus::TotalFailure,
+            _ => ExitStatus::PartialFailure,
+        }
+    }
+}
+
+fn print_summary(summary: &Summary) {
+    println!(
+        "Summary: {} succeeded, {} failed, {} nothing to do.",
+        summary.succeeded, summary.failed, summary.nothing_to_do
+    );
+}
+
+/// Print outcomes as text, or convert them to records for structured formats
+fn report<T>(
+    format: Format,
+    outcomes: &[Outcome<T>],
+    text: impl Fn(&Outcome<T>),
+    records: impl Fn(&Outcome<T>) -> Vec<Record>,
+) -> ExitStatus {
+    let summary = Summary::of(outcomes);
+    match format {
+        Format::Text => {
+            outcomes.iter().for_each(text);
+            print_summary(&summary);
+        }
+        _ => print_records(format, &outcomes.iter().flat_map(records).collect::<Vec<_>>()),
+    }
+    summary.into()
+}
+
+// run the operation, or only collect its changes in a dry run
+fn run_op<T>(args: &DryRunArgs, op: impl FnOnce() -> T) -> (T, Option<Vec<Change>>) {
+    match args.dry_run {
+        true => {
+            let (result, changes) = dry_run::run(op);
+            (result, Some(changes))
+        }
+        false => (op(), None),
+    }
+}
+
+/// Report outcomes as `report()` does, followed by changes of a dry run
+fn report_changes<T>(
+    format: Format,
+    (outcomes, changes): (Vec<Outcome<T>>, Option<Vec<Change>>),
+    text: impl Fn(&Outcome<T>),
+    records: impl Fn(&Outcome<T>) -> Vec<Record>,
+) -> ExitStatus {
+    let status = report(format, &outcomes, text, records);
+    if let Some(changes) = changes {
+        print_changes(format, &changes);
+    }
+    status
+}
+
+fn show_proxy(args: &ProxyableAppArgs, scope: Option<Scope>, format: Format) -> ExitStatus {
+    let outcomes = lane::get_proxies(args.app.as_deref(), scope);
+    let text = |o: &Outcome<_>| print_proxies_result(o.app, &o.result);
+    report(format, &outcomes, text, proxies_records)
+}
+
+fn clear_proxy(args: &ClearProxyArgs, scope: Option<Scope>, format: Format) -> ExitStatus {
+    let app = args.app.as_deref();
+    let run = run_op(&args.dry_run, || match (&args.url, args.all_urls) {
+        (Some(url), _) => lane::unset_url_proxy(app, scope, Some(url)),
+        (None, true) => lane::unset_url_proxy(app, scope, None),
+        (None, false) => lane::unset_proxy(app, scope),
+    });
+    let text = |o: &Outcome<_>| print_result(o.app, CLEAR_PROXY, &o.result);
+    report_changes(format, run, text, result_records)
+}
+
+fn set_proxy(args: &SetProxyArgs, scope: Option<Scope>, format: Format) -> ExitStatus {
+    let app = args.app.as_deref();
+    let run = run_op(&args.dry_run, || match &args.url {
+        Some(url) => lane::set_url_proxy(app, scope, url, &args.proxy),
+        None => lane::set_proxy(app, scope, &args.proxy, &args.no_proxy),
+    });
+    let text = |o: &Outcome<_>| print_result(o.app, SET_PROXY, &o.result);
+    report_changes(format, run, text, result_records)
+}
+
+fn show_mirror(args: &MirrorableAppArgs, scope: Option<Scope>, format: Format) -> ExitStatus {
+    if args.package_scopes {
+        let outcomes = lane::get_scoped_mirrors(args.app.as_deref(), scope);
+        let text = |o: &Outcome<_>| print_mirrors_result(o.app, &o.result);
+        return report(format, &outcomes, text, mirrors_records);
+    }
+    if args.extra_mirrors {
+        let outcomes = lane::get_extra_mirrors(args.app.as_deref(), scope);
+        let text = |o: &Outcome<_>| print_mirrors_result(o.app, &o.result);
+        return report(format, &outcomes, text, mirrors_records);
+    }
+    let outcomes = lane::get_mirror(args.app.as_deref(), scope);
+    let text = |o: &Outcome<_>| print_mirror_result(o.app, &o.result);
+    report(format, &outcomes, text, mirror_records)
+}
+
+fn clear_mirror(args: &ClearMirrorArgs, scope: Option<Scope>, format: Format) -> ExitStatus {
+    let app = args.app.as_deref();
+    let run = run_op(&args.dry_run, || match args {
+        ClearMirrorArgs { package_scope: Some(package_scope), .. } => {
+            lane::unset_scoped_mirror(app, scope, Some(package_scope))
+        }
+        ClearMirrorArgs { all_package_scopes: true, .. } => {
+            lane::unset_scoped_mirror(app, scope, None)
         }
+        ClearMirrorArgs { extra_mirror: Some(mirror), .. } => {
+            lane::unset_extra_mirror(app, scope, Some(mirror))
+        }
+        ClearMirrorArgs { all_extra_mirrors: true, .. } => {
+            lane::unset_extra_mirror(app, scope, None)
+        }
+        _ => lane::unset_mirror(app, scope),
+    });
+    let text = |o: &Outcome<_>| print_result(o.app, CLEAR_MIRROR, &o.result);
+    report_changes(format, run, text, result_records)
+}
+
+// auth token of the mirror read from the env var, so it's not left in shell history
+fn read_auth_token(var: Option<&str>) -> Result<Option<String>, LaneError> {
+    let read = |var| match env::var(var) {
+        Ok(token) if !token.is_empty() => Ok(token),
+        _ => Err(LaneError::Failure(format!("env {} of the auth token is not set", var))),
     };
-    ($args: ident, $app: ident, $func: expr, $work: ident, $printer: ident) => {
-        if $args.app == None || $args.app == Some($app) {
-            $printer($app, $work, &$func());
+    var.map(read).transpose()
+}
+
+fn set_mirror(args: &SetMirrorArgs, scope: Option<Scope>, format: Format) -> ExitStatus {
+    let run = match read_auth_token(args.auth_token_env.as_deref()) {
+        Ok(token) => run_op(&args.dry_run, || {
+            let (app, mirror, token) = (&args.app, &args.mirror, token.as_deref());
+            match &args.package_scope {
+                Some(package_scope) => {
+                    lane::set_scoped_mirror(app, scope, package_scope, mirror, token)
+                }
+                None if args.extra => lane::add_extra_mirror(app, scope, mirror, token),
+                None => lane::set_mirror(app, scope, mirror, token),
+            }
+        }),
+        Err(e) => {
+            let app = find_app(&args.app).map_or("", |app| app.name());
+            (vec![Outcome { app, result: Err(e) }], None)
         }
     };
-    ($args: ident, $app: ident, $func: expr, $param: ident, $work: ident, $printer: ident) => {
-        if $args.app == None || $args.app == Some($app) {
-            $printer($app, $work, &$func(&$args.$param));
+    let text = |o: &Outcome<()>| {
+        print_result(o.app, SET_MIRROR, &o.result);
+        if let (Err(LaneError::UnknownMirror(_)), Some(app)) = (&o.result, find_app(o.app)) {
+            let names: Vec<String> =
+                app.mirrors().unwrap_or_default().into_iter().map(|m| m.name).collect();
+            println!("{}: Available mirrors: {}", app.name(), names.join(", "));
         }
     };
+    report_changes(format, run, text, result_records)
+}
+
+fn use_profile(args: &UseArgs, scope: Option<Scope>, format: Format) -> ExitStatus {
+    match run_op(&args.dry_run, || lane::use_profile(&args.profile, scope)) {
+        (Ok(outcomes), changes) => {
+            let text = |o: &Outcome<_>| print_result(o.app, USE_PROFILE, &o.result);
+            report_changes(format, (outcomes, changes), text, result_records)
+        }
+        // no app is run, so the record of the error has no app
+        (Err(e), _) if format != Format::Text => {
+            print_records(format, &[Record { error: Some(e.to_string()), ..Default::default() }]);
+            ExitStatus::TotalFailure
+        }
+        (Err(e), _) => {
+            println!("{}: {} failed! Error: {}", args.profile, USE_PROFILE, e);
+            ExitStatus::TotalFailure
+        }
+    }
 }
 
-fn show_proxy(args: &ProxyableAppArgs) {
-    use ProxyableApps::*;
-    do_work!(args, Curl, curl::get_proxy, print_proxy_option);
-    do_work!(args, Git, git::get_proxies, print_proxies_result);
-    do_work!(args, Cargo, cargo::get_proxy, print_proxy_option);
+fn print_profile(name: &str, profile: &Profile) {
+    println!("{}:", name);
+    println!("    proxy: {}", profile.proxy.as_deref().unwrap_or("none"));
+    if !profile.no_proxy.is_empty() {
+        println!("    no-proxy: {}", profile.no_proxy.join(","));
+    }
+    for (app, mirror) in &profile.mirrors {
+        println!("    mirror of {}: {}", app, mirror);
+    }
 }
 
-fn clear_proxy(args: &ProxyableAppArgs) {
-    use ProxyableApps::*;
-    do_work!(args, Curl, curl::unset_proxy, CLEAR_PROXY, print_result);
-    do_work!(args, Git, git::unset_proxy, CLEAR_PROXY, print_result);
-    do_work!(args, Cargo, cargo::unset_proxy, CLEAR_PROXY, print_result);
+fn exit_status_of(result: &Result<(), LaneError>) -> ExitStatus {
+    match result {
+        Ok(()) => ExitStatus::Success,
+        Err(LaneError::NothingToDo()) => ExitStatus::NothingToDo,
+        Err(_) => ExitStatus::TotalFailure,
+    }
 }
 
-fn set_proxy(args: &SetProxyArgs) {
-    use ProxyableApps::*;
-    do_work!(args, Curl, curl::set_proxy, proxy, SET_PROXY, print_result);
-    do_work!(args, Git, git::set_proxy, proxy, SET_PROXY, print_result);
-    do_work!(args, Cargo, cargo::set_proxy, proxy, SET_PROXY, print_result);
+fn list_profiles(format: Format) -> Result<(), LaneError> {
+    let config = Config::load()?;
+    if format != Format::Text {
+        let records: Vec<ProfileRecord> = config
+            .profiles
+            .iter()
+            .map(|(name, profile)| ProfileRecord::new(name, Some(profile), None))
+            .collect();
+        print_records(format, &records);
+        return Ok(());
+    }
+    if config.profiles.is_empty() {
+        println!("{}", PROMPT_NO_PROFILE);
+    }
+    config.profiles.keys().for_each(|name| println!("{}", name));
+    Ok(())
 }
 
-fn show_mirror(args: &MirrorableAppArgs) {
-    use MirrorableApps::*;
-    do_work!(args, Cargo, cargo::get_mirror, print_mirror_result);
-    do_work!(args, Npm, npm::get_mirror, print_mirror_result);
+fn show_profile(name: &str, format: Format) -> Result<(), LaneError> {
+    let config = Config::load()?;
+    let profile = config.profile(name)?;
+    match format {
+        Format::Text => print_profile(name, profile),
+        _ => print_records(format, &[ProfileRecord::new(name, Some(profile), None)]),
+    }
+    Ok(())
 }
 
-fn clear_mirror(args: &MirrorableAppArgs) {
-    use MirrorableApps::*;
-    do_work!(args, Cargo, cargo::unset_mirror, CLEAR_MIRROR, print_result);
-    do_work!(args, Npm, npm::unset_mirror, CLEAR_MIRROR, print_result);
+// result of adding or removing a profile
+fn report_profile(
+    format: Format,
+    name: &str,
+    work: &str,
+    profile: Option<&Profile>,
+    result: &Result<(), LaneError>,
+) {
+    match format {
+        Format::Text => print_result(name, work, result),
+        _ => print_records(format, &[ProfileRecord::new(name, profile, result.as_ref().err())]),
+    }
 }
 
-fn set_mirror(app: &MirrorableAppsWithParam) {
-    use MirrorableApps::*;
-    macro_rules! gen_match {
-        ($app: ident, $($mirror:ident, $func:expr), *) => {
-            match $app {
-                $(MirrorableAppsWithParam::$mirror {mirror} => {
-                    print_result($mirror, SET_MIRROR, &$func(mirror));
-                }) *
+fn manage_profile(args: ProfileArgs, format: Format) -> ExitStatus {
+    let (name, result) = match args.command {
+        ProfileCommands::List => (String::new(), list_profiles(format)),
+        ProfileCommands::Show(args) => {
+            let result = show_profile(&args.name, format);
+            (args.name, result)
+        }
+        ProfileCommands::Add(args) => {
+            let profile = Profile {
+                proxy: args.proxy,
+                no_proxy: args.no_proxy,
+                mirrors: args.mirror.into_iter().collect(),
+            };
+            let result = lane::add_profile(&args.name, &profile);
+            report_profile(format, &args.name, ADD_PROFILE, Some(&profile), &result);
+            return exit_status_of(&result);
+        }
+        ProfileCommands::Remove(args) => {
+            let result = config::remove_profile(&args.name);
+            report_profile(format, &args.name, REMOVE_PROFILE, None, &result);
+            return exit_status_of(&result);
+        }
+    };
+    match (&result, format) {
+        (Err(e), Format::Text) => println!("Error: {}", e),
+        (Err(e), _) => print_records(format, &[ProfileRecord::new(&name, None, Some(e))]),
+        _ => {}
+    }
+    exit_status_of(&result)
+}
+
+// format seconds since unix epoch as UTC time, like "2022-08-01 12:00:00 UTC"
+fn format_timestamp(timestamp: u64) -> String {
+    let (days, secs) = (timestamp / 86400, timestamp % 86400);
+    // civil from days, refer to http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+fn print_entry(index: usize, entry: &Entry) {
+    println!("{}: {} {}", index, format_timestamp(entry.timestamp), entry.command);
+    for change in &entry.changes {
+        println!("    {} {}", change_action(change), change.path.display());
+    }
+}
+
+fn show_history(format: Format) -> ExitStatus {
+    if format != Format::Text {
+        let records = match journal::load() {
+            Ok(entries) => {
+                let records = entries.iter().enumerate();
+                records.flat_map(|(i, entry)| change_records(Some(i + 1), entry)).collect()
             }
+            Err(e) => vec![ChangeRecord::error(&e)],
+        };
+        print_records(format, &records);
+        return match records.first() {
+            Some(ChangeRecord { error: Some(_), .. }) => ExitStatus::TotalFailure,
+            _ => ExitStatus::Success,
+        };
+    }
+    match journal::load() {
+        Ok(entries) if entries.is_empty() => {
+            println!("{}", PROMPT_NO_HISTORY);
+            ExitStatus::Success
+        }
+        Ok(entries) => {
+            entries.iter().enumerate().for_each(|(i, entry)| print_entry(i + 1, entry));
+            ExitStatus::Success
+        }
+        Err(e) => {
+            println!("Failed to load history! Error: {}", e);
+            ExitStatus::TotalFailure
         }
     }
-    gen_match!(app, Cargo, cargo::set_mirror, Npm, npm::set_mirror);
 }
 
-pub fn handle_cli_args(cli: Cli) {
+fn undo(args: &UndoArgs, format: Format) -> ExitStatus {
+    let result = journal::undo(args.force);
+    match &result {
+        Ok(entry) if format != Format::Text => print_records(format, &change_records(None, entry)),
+        Err(e) if format != Format::Text => print_records(format, &[ChangeRecord::error(e)]),
+        Ok(entry) => println!("{} succeeded! Reverted: {}", UNDO, entry.command),
+        Err(LaneError::NothingToDo()) => println!("{}", PROMPT_NO_HISTORY),
+        Err(e) => println!("{} failed! Error: {}", UNDO, e),
+    }
+    exit_status_of(&result.map(|_| ()))
+}
+
+pub fn handle_cli_args(cli: Cli) -> ExitStatus {
     use Commands::*;
+    let format = cli.format;
     match cli.command {
-        GetProxy(args) => show_proxy(&args),
-        ClearProxy(args) => clear_proxy(&args),
-        SetProxy(args) => set_proxy(&args),
-        GetMirror(args) => show_mirror(&args),
-        ClearMirror(args) => clear_mirror(&args),
-        SetMirror { app } => set_mirror(&app),
+        GetProxy(args) => show_proxy(&args, cli.scope, format),
+        ClearProxy(args) => clear_proxy(&args, cli.scope, format),
+        SetProxy(args) => set_proxy(&args, cli.scope, format),
+        GetMirror(args) => show_mirror(&args, cli.scope, format),
+        ClearMirror(args) => clear_mirror(&args, cli.scope, format),
+        SetMirror(args) => set_mirror(&args, cli.scope, format),
+        Use(args) => use_profile(&args, cli.scope, format),
+        Profile(args) => manage_profile(args, format),
+        Undo(args) => undo(&args, format),
+        History => show_history(format),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(succeeded: usize, failed: usize, nothing_to_do: usize) -> Summary {
+        Summary { succeeded, failed, nothing_to_do }
+    }
+
+    #[test]
+    fn exit_status_of_summary() {
+        assert_eq!(ExitStatus::from(summary(2, 0, 1)), ExitStatus::Success);
+        assert_eq!(ExitStatus::from(summary(0, 2, 1)), ExitStatus::TotalFailure);
+        assert_eq!(ExitStatus::from(summary(1, 1, 0)), ExitStatus::PartialFailure);
+        assert_eq!(ExitStatus::from(summary(0, 0, 2)), ExitStatus::NothingToDo);
+        assert_eq!(ExitStatus::from(summary(0, 0, 0)), ExitStatus::NothingToDo);
+        let codes = [
+            ExitStatus::Success,
+            ExitStatus::TotalFailure,
+            ExitStatus::PartialFailure,
+            ExitStatus::NothingToDo,
+        ]
+        .map(|status| status as i32);
+        assert_eq!(codes, [0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn exit_status_of_result() {
+        assert_eq!(exit_status_of(&Ok(())), ExitStatus::Success);
+        assert_eq!(exit_status_of(&Err(LaneError::NothingToDo())), ExitStatus::NothingToDo);
+        assert_eq!(exit_status_of(&Err(LaneError::NoHomeDir())), ExitStatus::TotalFailure);
+    }
+
+    #[test]
+    fn read_auth_token_of_unset_env() {
+        assert_eq!(read_auth_token(None), Ok(None));
+        assert!(read_auth_token(Some("LANE_TEST_UNSET_TOKEN")).is_err());
     }
 }
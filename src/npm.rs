@@ -1,11 +1,42 @@
 use clap::clap_derive::ArgEnum;
-use strum::{AsRefStr, EnumMessage, EnumIter, IntoEnumIterator};
+use dirs::home_dir;
+use log::warn;
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+use strum::{AsRefStr, EnumIter, EnumMessage};
 
-use crate::{utils::*, error::{LaneError, make_command_failed_error}};
+use crate::{
+    app::*,
+    error::*,
+    npmrc::{expand_env, Npmrc},
+    utils::*,
+};
 
-// Note: registry must end with slash('/') because "npm config set registry" command will add one
-const DEFAULT_REGISTRY: &str = "https://registry.npmjs.org/";
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ArgEnum, AsRefStr, EnumMessage, EnumIter)]
+// npm: https://docs.npmjs.com/cli/configuring-npm/npmrc
+// Note: registry must end with slash('/') as npm appends paths to it
+const KEY_REGISTRY: &str = "registry";
+const KEY_PROXY: &str = "proxy";
+const KEY_HTTPS_PROXY: &str = "https-proxy";
+const KEY_NO_PROXY: &str = "noproxy";
+const SUFFIX_SCOPED_REGISTRY: &str = ":registry";
+pub(crate) const NPM_CONFIG: &str = ".npmrc";
+const NPM_GLOBAL_CONFIG: &str = "etc/npmrc";
+#[cfg(not(target_os = "windows"))]
+const NPM_BUILTIN_CONFIG: &str = "lib/node_modules/npm/npmrc";
+#[cfg(target_os = "windows")]
+const NPM_BUILTIN_CONFIG: &str = "node_modules/npm/npmrc";
+const NPM_ENV_PREFIX: &str = "npm_config_";
+const KEY_USER_CONFIG: &str = "userconfig";
+const KEY_GLOBAL_CONFIG: &str = "globalconfig";
+const KEY_PREFIX: &str = "prefix";
+const PACKAGE_JSON: &str = "package.json";
+const NODE_MODULES: &str = "node_modules";
+const PROMPT_NO_PREFIX: &str = "Cannot find npm global prefix";
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ArgEnum, AsRefStr, EnumMessage, EnumIter,
+)]
 pub enum Mirrors {
     #[strum(message = "https://registry.npmmirror.com/")]
     Taobao,
@@ -13,35 +44,469 @@ pub enum Mirrors {
     Huawei,
 }
 
-fn from_message(registry: &str) -> Result<Mirrors, LaneError> {
-    for mirror in Mirrors::iter() {
-        if mirror.get_message().unwrap() == registry {
-            return Ok(mirror);
+fn get_env_name(key: &str) -> String {
+    format!("{}{}", NPM_ENV_PREFIX, key.replace('-', "_"))
+}
+
+// npm reads env vars like "npm_config_registry" case insensitively, with '_' in place of '-'
+fn get_env_config(key: &str) -> Option<String> {
+    let name = get_env_name(key);
+    env::vars()
+        .find(|(k, v)| k.eq_ignore_ascii_case(&name) && !v.is_empty())
+        .map(|(_, value)| value)
+}
+
+fn read_config(path: &Path) -> Result<Npmrc, LaneError> {
+    match path.exists() {
+        true => Ok(Npmrc::parse(&read_file(path).map_err(|_| make_invalid_file_error(path))?)),
+        false => Ok(Npmrc::default()),
+    }
+}
+
+// value of the key in the file, with env vars expanded
+fn read_config_value(path: &Path, key: &str) -> Option<String> {
+    read_config(path).ok()?.get(key).map(expand_env)
+}
+
+// the project is the nearest dir with package.json or node_modules, or current dir if none
+pub(crate) fn get_project_dir() -> Result<PathBuf, LaneError> {
+    let current_dir = env::current_dir().map_err(make_failure_error)?;
+    let project_dir = current_dir
+        .ancestors()
+        .find(|dir| dir.join(PACKAGE_JSON).is_file() || dir.join(NODE_MODULES).is_dir())
+        .map(Path::to_path_buf);
+    Ok(project_dir.unwrap_or(current_dir))
+}
+
+pub(crate) fn get_user_config_path() -> Result<PathBuf, LaneError> {
+    match get_env_config(KEY_USER_CONFIG) {
+        Some(file) => Ok(PathBuf::from(expand_env(&file))),
+        None => Ok(home_dir().ok_or(LaneError::NoHomeDir())?.join(NPM_CONFIG)),
+    }
+}
+
+// prefix containing bin/node, where npm is installed
+fn get_node_prefix() -> Option<PathBuf> {
+    let paths = env::var_os("PATH").unwrap_or_default();
+    env::split_paths(&paths)
+        .map(|dir| dir.join("node"))
+        .find(|node| node.is_file())
+        .and_then(|node| node.canonicalize().ok())
+        .and_then(|node| node.parent()?.parent().map(Path::to_path_buf))
+}
+
+// npmrc shipped with npm, read only
+fn get_builtin_config_path() -> Option<PathBuf> {
+    Some(get_node_prefix()?.join(NPM_BUILTIN_CONFIG))
+}
+
+// $PREFIX is set by env, user config or builtin config, or the prefix of node by default. It's
+// unknown when node isn't installed.
+fn get_global_prefix() -> Option<PathBuf> {
+    let configured = get_env_config(KEY_PREFIX)
+        .or_else(|| read_config_value(&get_user_config_path().ok()?, KEY_PREFIX))
+        .or_else(|| read_config_value(&get_builtin_config_path()?, KEY_PREFIX));
+    configured.map(PathBuf::from).or_else(get_node_prefix)
+}
+
+// file written in the scope, `None` for system scope without a global prefix
+fn get_config_file_path(scope: Scope) -> Result<Option<PathBuf>, LaneError> {
+    match scope {
+        Scope::Project => Ok(Some(get_project_dir()?.join(NPM_CONFIG))),
+        Scope::User => get_user_config_path().map(Some),
+        Scope::System => match get_env_config(KEY_GLOBAL_CONFIG) {
+            Some(file) => Ok(Some(PathBuf::from(expand_env(&file)))),
+            None => Ok(get_global_prefix().map(|prefix| prefix.join(NPM_GLOBAL_CONFIG))),
+        },
+    }
+}
+
+// files read in the scope, the builtin config is the lowest layer of system scope
+fn get_config_files(scope: Scope) -> Result<NpmrcFiles, LaneError> {
+    let path = get_config_file_path(scope)?;
+    let mut files: Vec<PathBuf> = path.iter().cloned().collect();
+    if scope == Scope::System {
+        files.extend(get_builtin_config_path());
+    }
+    Ok(NpmrcFiles { scope, path, files })
+}
+
+// values set by env override all config files
+fn warn_on_env_override(key: &str) {
+    if let Some(value) = get_env_config(key) {
+        warn!("{} is overridden by env {} as {}", key, get_env_name(key), value);
+    }
+}
+
+// auth token of a registry is set by key like "//registry.npmjs.org/:_authToken"
+pub(crate) fn get_auth_token_key(registry: &str) -> String {
+    let registry = registry.split_once("://").map(|(_, rest)| rest).unwrap_or(registry);
+    format!("//{}/:_authToken", registry.trim_end_matches('/'))
+}
+
+// "corp" or "@corp" is accepted as package scope "@corp"
+fn normalize_package_scope(package_scope: &str) -> Result<String, LaneError> {
+    let name = package_scope.strip_prefix('@').unwrap_or(package_scope);
+    let is_valid_char = |c: char| c.is_ascii_alphanumeric() || "-._~".contains(c);
+    match !name.is_empty() && name.chars().all(is_valid_char) {
+        true => Ok(format!("@{}", name)),
+        false => Err(LaneError::InvalidPackageScope(package_scope.to_string())),
+    }
+}
+
+// registry of a package scope is set by key like "@corp:registry"
+fn package_scope_of_key(key: &str) -> Option<&str> {
+    key.strip_suffix(SUFFIX_SCOPED_REGISTRY).filter(|scope| scope.starts_with('@'))
+}
+
+fn is_registry_key(key: &str) -> bool {
+    key == KEY_REGISTRY || package_scope_of_key(key).is_some()
+}
+
+// remove registries of matching keys, and auth tokens not used by other registries any more
+fn unset_registries(config: &mut Npmrc, matches: impl Fn(&str) -> bool) -> bool {
+    let removed: Vec<(String, String)> = config
+        .entries()
+        .filter(|(key, _)| is_registry_key(key) && matches(key))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    for (key, _) in &removed {
+        config.unset(key);
+    }
+    for (_, registry) in &removed {
+        let token_key = get_auth_token_key(registry);
+        let used = config
+            .entries()
+            .any(|(key, value)| is_registry_key(key) && get_auth_token_key(value) == token_key);
+        if !used {
+            config.unset(&token_key);
         }
     }
-    Err(LaneError::UnknownMirror(registry.to_string()))
+    !removed.is_empty()
+}
+
+// remove registries of package scopes matching `package_scope` (all when `None`)
+fn unset_scoped_registries(config: &mut Npmrc, package_scope: Option<&str>) -> bool {
+    unset_registries(config, |key| match package_scope_of_key(key) {
+        Some(scope) => package_scope.is_none_or(|p| p == scope),
+        None => false,
+    })
 }
 
-pub fn get_mirror() -> Result<Option<Mirrors>, LaneError> {
-    let registry_string = exec2("npm", ["config", "get", "registry"])?;
-    let registry = registry_string.trim();
-    if registry == DEFAULT_REGISTRY {
-        return Ok(None);
+/// Npmrc files of a scope. npm and pnpm read the same keys from different files, so both manage
+/// them through this.
+pub(crate) struct NpmrcFiles {
+    pub scope: Scope,
+    /// File written in the scope, `None` when it can't be found, like the global config of npm
+    /// without node installed. Nothing is read from it then, and writing it fails.
+    pub path: Option<PathBuf>,
+    /// Files read in the scope, the former one wins
+    pub files: Vec<PathBuf>,
+}
+
+impl NpmrcFiles {
+    // the value in effect of the scope, with the file and key setting it. Env vars override all
+    // config files, so they are reported as the project scope, which has the highest precedence.
+    fn read_value(&self, key: &str) -> Option<(Option<PathBuf>, String, String)> {
+        if let Some(value) = get_env_config(key).filter(|_| self.scope == Scope::Project) {
+            return Some((None, get_env_name(key), value));
+        }
+        self.files.iter().find_map(|file| {
+            Some((Some(file.clone()), key.to_string(), read_config_value(file, key)?))
+        })
+    }
+
+    // masked auth token of the registry, in the first config file having it
+    fn read_auth(&self, registry: &str) -> Option<String> {
+        let key = get_auth_token_key(registry);
+        self.files.iter().find_map(|file| read_config(file).ok()?.get(&key).map(mask_secret))
+    }
+
+    // edit the config file of the scope, `edit` returns false if nothing is changed
+    fn update(&self, edit: impl FnOnce(&mut Npmrc) -> bool) -> Result<(), LaneError> {
+        let path = self.path.as_ref().ok_or_else(|| make_failure_error(PROMPT_NO_PREFIX))?;
+        let mut config = read_config(path)?;
+        if !edit(&mut config) {
+            return Err(LaneError::NothingToDo());
+        }
+        write_config_file(path, &config.to_string())
+    }
+
+    // keys other than "proxy" are shown as subsections to tell them apart
+    pub fn get_proxies(&self) -> Result<Vec<Proxy>, LaneError> {
+        let mut proxies = vec![];
+        for key in [KEY_PROXY, KEY_HTTPS_PROXY, KEY_NO_PROXY] {
+            if let Some((path, source, value)) = self.read_value(key) {
+                let mut proxy = Proxy::new(self.scope, path.as_deref(), &source, value);
+                proxy.subsection = (key != KEY_PROXY).then(|| key.to_string());
+                proxies.push(proxy);
+            }
+        }
+        Ok(proxies)
+    }
+
+    // "proxy" is used for http requests and "https-proxy" for https ones
+    pub fn set_proxy(&self, proxy: &str) -> Result<(), LaneError> {
+        validate_proxy_url(proxy)?;
+        warn_on_env_override(KEY_PROXY);
+        warn_on_env_override(KEY_HTTPS_PROXY);
+        self.update(|config| {
+            config.set(KEY_PROXY, proxy);
+            config.set(KEY_HTTPS_PROXY, proxy);
+            true
+        })
+    }
+
+    pub fn unset_proxy(&self) -> Result<(), LaneError> {
+        self.update(|config| config.unset(KEY_PROXY) | config.unset(KEY_HTTPS_PROXY))
+    }
+
+    pub fn set_no_proxy(&self, hosts: &[String]) -> Result<(), LaneError> {
+        warn_on_env_override(KEY_NO_PROXY);
+        self.update(|config| {
+            config.set(KEY_NO_PROXY, &hosts.join(","));
+            true
+        })
+    }
+
+    pub fn unset_no_proxy(&self) -> Result<(), LaneError> {
+        self.update(|config| config.unset(KEY_NO_PROXY))
+    }
+
+    pub fn get_mirror(&self, app: &dyn App) -> Result<Option<Mirror>, LaneError> {
+        // the default registry set explicitly overrides mirrors of latter scopes
+        let (config_file, key, registry) = match self.read_value(KEY_REGISTRY) {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let name = app.find_mirror_by_url(&registry)?.map(|mirror| mirror.name);
+        let mut mirror = Mirror::new(self.scope, config_file.as_deref(), &key, name, &registry);
+        mirror.auth = self.read_auth(&registry);
+        Ok(Some(mirror))
+    }
+
+    pub fn set_mirror(&self, mirror: &MirrorEntry) -> Result<(), LaneError> {
+        warn_on_env_override(KEY_REGISTRY);
+        self.update(|config| {
+            config.set(KEY_REGISTRY, &mirror.url);
+            if let Some(token) = &mirror.auth {
+                config.set(&get_auth_token_key(&mirror.url), token);
+            }
+            true
+        })
+    }
+
+    pub fn unset_mirror(&self) -> Result<(), LaneError> {
+        self.update(|config| unset_registries(config, |key| key == KEY_REGISTRY))
+    }
+
+    pub fn get_scoped_mirrors(&self, app: &dyn App) -> Result<Vec<Mirror>, LaneError> {
+        let mut mirrors: Vec<Mirror> = vec![];
+        for config_file in &self.files {
+            let config = read_config(config_file)?;
+            for (key, _) in config.entries() {
+                let package_scope = match package_scope_of_key(key) {
+                    // the former file wins, and the last value in a file
+                    Some(p) if !mirrors.iter().any(|m| m.subsection.as_deref() == Some(p)) => p,
+                    _ => continue,
+                };
+                let registry = expand_env(config.get(key).unwrap_or_default());
+                let name = app.find_mirror_by_url(&registry)?.map(|mirror| mirror.name);
+                let mut mirror = Mirror::new(self.scope, Some(config_file), key, name, &registry);
+                mirror.subsection = Some(package_scope.to_string());
+                mirror.auth = self.read_auth(&registry);
+                mirrors.push(mirror);
+            }
+        }
+        Ok(mirrors)
+    }
+
+    pub fn set_scoped_mirror(
+        &self,
+        package_scope: &str,
+        mirror: &MirrorEntry,
+    ) -> Result<(), LaneError> {
+        let key = normalize_package_scope(package_scope)? + SUFFIX_SCOPED_REGISTRY;
+        self.update(|config| {
+            config.set(&key, &mirror.url);
+            if let Some(token) = &mirror.auth {
+                config.set(&get_auth_token_key(&mirror.url), token);
+            }
+            true
+        })
+    }
+
+    pub fn unset_scoped_mirror(&self, package_scope: Option<&str>) -> Result<(), LaneError> {
+        let package_scope = package_scope.map(normalize_package_scope).transpose()?;
+        self.update(|config| unset_scoped_registries(config, package_scope.as_deref()))
     }
-    let mirror = from_message(registry)?;
-    Ok(Some(mirror))
 }
 
-pub fn set_mirror(mirror: &Mirrors) -> Result<(), LaneError> {
-    if !exec("npm", ["config", "set", "registry", mirror.get_message().unwrap()]) {
-        return Err(make_command_failed_error("npm set registry"));
+pub struct Npm;
+
+impl App for Npm {
+    fn name(&self) -> &'static str {
+        "npm"
+    }
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::Proxy, Capability::NoProxy, Capability::Mirror, Capability::ScopedMirror]
+    }
+
+    fn scopes(&self) -> &'static [Scope] {
+        &[Scope::Project, Scope::User, Scope::System]
+    }
+
+    fn config_files(&self, scope: Scope) -> Vec<PathBuf> {
+        get_config_file_path(scope).ok().flatten().into_iter().collect()
+    }
+
+    fn get_proxies(&self, scope: Scope) -> Result<Vec<Proxy>, LaneError> {
+        get_config_files(scope)?.get_proxies()
+    }
+
+    fn set_proxy(&self, scope: Scope, proxy: &str) -> Result<(), LaneError> {
+        get_config_files(scope)?.set_proxy(proxy)
+    }
+
+    fn unset_proxy(&self, scope: Scope) -> Result<(), LaneError> {
+        get_config_files(scope)?.unset_proxy()
+    }
+
+    fn set_no_proxy(&self, scope: Scope, hosts: &[String]) -> Result<(), LaneError> {
+        get_config_files(scope)?.set_no_proxy(hosts)
+    }
+
+    fn unset_no_proxy(&self, scope: Scope) -> Result<(), LaneError> {
+        get_config_files(scope)?.unset_no_proxy()
+    }
+
+    fn builtin_mirrors(&self) -> Vec<MirrorEntry> {
+        mirror_entries::<Mirrors>()
+    }
+
+    fn get_mirror(&self, scope: Scope) -> Result<Option<Mirror>, LaneError> {
+        get_config_files(scope)?.get_mirror(self)
+    }
+
+    fn set_mirror(&self, scope: Scope, mirror: &MirrorEntry) -> Result<(), LaneError> {
+        get_config_files(scope)?.set_mirror(mirror)
+    }
+
+    fn unset_mirror(&self, scope: Scope) -> Result<(), LaneError> {
+        get_config_files(scope)?.unset_mirror()
+    }
+
+    fn get_scoped_mirrors(&self, scope: Scope) -> Result<Vec<Mirror>, LaneError> {
+        get_config_files(scope)?.get_scoped_mirrors(self)
+    }
+
+    fn set_scoped_mirror(
+        &self,
+        scope: Scope,
+        package_scope: &str,
+        mirror: &MirrorEntry,
+    ) -> Result<(), LaneError> {
+        get_config_files(scope)?.set_scoped_mirror(package_scope, mirror)
+    }
+
+    fn unset_scoped_mirror(
+        &self,
+        scope: Scope,
+        package_scope: Option<&str>,
+    ) -> Result<(), LaneError> {
+        get_config_files(scope)?.unset_scoped_mirror(package_scope)
     }
-    Ok(())
 }
 
-pub fn unset_mirror() -> Result<(), LaneError> {
-    if !exec("npm", ["config", "delete", "registry"]) {
-        return Err(make_command_failed_error("npm delete registry"));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NPMRC: &str = "registry=https://registry.npmmirror.com/\n\
+                         @corp:registry=https://npm.corp/\n\
+                         //npm.corp/:_authToken=${NPM_TOKEN}\n\
+                         @team:registry=https://npm.corp/\n\
+                         @other:registry=https://npm.other/\n\
+                         //npm.other/:_authToken=secret\n";
+
+    #[test]
+    fn parse_package_scope() {
+        assert_eq!(normalize_package_scope("corp").unwrap(), "@corp");
+        assert_eq!(normalize_package_scope("@corp").unwrap(), "@corp");
+        assert!(normalize_package_scope("@").is_err());
+        assert!(normalize_package_scope("@corp:registry").is_err());
+        assert_eq!(package_scope_of_key("@corp:registry"), Some("@corp"));
+        assert_eq!(package_scope_of_key("registry"), None);
+    }
+
+    #[test]
+    fn unset_scoped_registries_with_tokens() {
+        let mut config = Npmrc::parse(NPMRC);
+        assert!(unset_scoped_registries(&mut config, Some("@corp")));
+        assert!(!unset_scoped_registries(&mut config, Some("@corp")));
+        // the token is still used by @team
+        assert_eq!(config.get("//npm.corp/:_authToken"), Some("${NPM_TOKEN}"));
+        assert!(unset_scoped_registries(&mut config, None));
+        assert_eq!(config.to_string(), "registry=https://registry.npmmirror.com/\n");
+    }
+
+    #[test]
+    fn read_without_global_prefix() {
+        let dir = env::temp_dir().join(format!("lane-test-npm-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let user_config = dir.join(NPM_CONFIG);
+        std::fs::write(&user_config, "proxy=http://p:1\n").unwrap();
+        let user = NpmrcFiles {
+            scope: Scope::User,
+            path: Some(user_config.clone()),
+            files: vec![user_config],
+        };
+        let proxies = user.get_proxies().unwrap();
+        assert_eq!(proxies.len(), 1);
+        assert_eq!(proxies[0].proxy, "http://p:1");
+
+        // nothing is read from the missing global config, but writing it fails
+        let system = NpmrcFiles { scope: Scope::System, path: None, files: vec![] };
+        assert_eq!(system.get_proxies().unwrap(), vec![]);
+        assert_eq!(system.set_proxy("http://p:1"), Err(make_failure_error(PROMPT_NO_PREFIX)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn set_and_unset_no_proxy() {
+        let dir = env::temp_dir().join(format!("lane-test-npm-no-proxy-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(NPM_CONFIG);
+        std::fs::write(&path, "registry=https://npm.corp/\n").unwrap();
+        let files =
+            NpmrcFiles { scope: Scope::User, path: Some(path.clone()), files: vec![path.clone()] };
+
+        files.set_proxy("http://p:1").unwrap();
+        files.set_no_proxy(&["localhost".to_string(), ".corp".to_string()]).unwrap();
+        let proxies = files.get_proxies().unwrap();
+        let no_proxy = proxies.iter().find(|p| p.subsection.as_deref() == Some(KEY_NO_PROXY));
+        assert_eq!(no_proxy.map(|p| p.proxy.as_str()), Some("localhost,.corp"));
+
+        files.unset_proxy().unwrap();
+        files.unset_no_proxy().unwrap();
+        assert_eq!(files.unset_no_proxy(), Err(LaneError::NothingToDo()));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "registry=https://npm.corp/\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unset_registry_with_token() {
+        let content = "registry=https://npm.corp/\n//npm.corp/:_authToken=secret\n";
+        let mut config =
+            Npmrc::parse(&(content.to_string() + "@corp:registry=https://npm.corp/\n"));
+        assert!(unset_registries(&mut config, |key| key == KEY_REGISTRY));
+        // the token is still used by @corp
+        assert_eq!(config.get("//npm.corp/:_authToken"), Some("secret"));
+
+        let mut config = Npmrc::parse(content);
+        assert!(unset_registries(&mut config, |key| key == KEY_REGISTRY));
+        assert!(!unset_registries(&mut config, |key| key == KEY_REGISTRY));
+        assert_eq!(config.to_string(), "");
     }
-    Ok(())
 }
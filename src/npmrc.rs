@@ -0,0 +1,230 @@
+// A format preserving editor of npmrc files, in the ini format of https://github.com/npm/ini
+//
+// Lines not modified are kept as is, including comments. npm only reads keys before the first
+// section, so keys in sections are kept but not managed.
+
+use std::{env, fmt::Display};
+
+#[derive(Clone, Debug)]
+struct Line {
+    text: String,
+    /// Key and value of a top level line
+    entry: Option<(String, String)>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Npmrc {
+    lines: Vec<Line>,
+}
+
+// unquote a value like ini does: quoted values are taken as is, while `;` and `#` start a comment
+// in others unless escaped by backslash
+fn parse_value(value: &str) -> String {
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            let inner = &value[1..value.len() - 1];
+            if quote == '"' {
+                if let Ok(unquoted) = serde_json::from_str::<String>(value) {
+                    return unquoted;
+                }
+            }
+            return inner.to_string();
+        }
+    }
+    let mut result = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(next @ ('\\' | ';' | '#')) => result.push(next),
+                Some(next) => {
+                    result.push(c);
+                    result.push(next);
+                }
+                None => result.push(c),
+            },
+            ';' | '#' => break,
+            _ => result.push(c),
+        }
+    }
+    result.trim().to_string()
+}
+
+// quote a value when it would be changed by `parse_value()`
+fn format_value(value: &str) -> String {
+    let needs_quote =
+        value.trim() != value || value.starts_with(['"', '\'']) || value.contains([';', '#', '\\']);
+    match needs_quote {
+        true => serde_json::to_string(value).unwrap_or_else(|_| value.to_string()),
+        false => value.to_string(),
+    }
+}
+
+// "key = value", or a bare "key" meaning true
+fn parse_entry(line: &str) -> Option<(String, String)> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with([';', '#']) {
+        return None;
+    }
+    match trimmed.split_once('=') {
+        Some((key, value)) => Some((parse_value(key), parse_value(value))),
+        None => Some((parse_value(trimmed), "true".to_string())),
+    }
+}
+
+fn is_section_header(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.starts_with('[') && trimmed.ends_with(']')
+}
+
+/// Replace `${VAR}` in the value with environment variables as npm does, leaving unknown ones as
+/// is. A backslash before `$` escapes it.
+pub fn expand_env(value: &str) -> String {
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let escaped = rest[..start].ends_with('\\');
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let name = &rest[start + 2..end];
+        match env::var(name) {
+            Ok(var) if !escaped => {
+                result += &rest[..start];
+                result += &var;
+            }
+            _ if escaped => {
+                result += &rest[..start - 1];
+                result += &rest[start..=end];
+            }
+            _ => result += &rest[..=end],
+        }
+        rest = &rest[end + 1..];
+    }
+    result + rest
+}
+
+impl Npmrc {
+    pub fn parse(content: &str) -> Self {
+        let mut lines = vec![];
+        let mut in_section = false;
+        for text in content.split('\n') {
+            in_section |= is_section_header(text);
+            let entry = if in_section { None } else { parse_entry(text) };
+            lines.push(Line { text: text.to_string(), entry });
+        }
+        Npmrc { lines }
+    }
+
+    // indexes of top level lines with the key
+    fn find(&self, key: &str) -> Vec<usize> {
+        let matches = |line: &Line| line.entry.as_ref().map(|(k, _)| k == key).unwrap_or(false);
+        (0..self.lines.len()).filter(|i| matches(&self.lines[*i])).collect()
+    }
+
+    /// Value of the key, the last one wins as in ini
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let index = *self.find(key).last()?;
+        self.lines[index].entry.as_ref().map(|(_, value)| value.as_str())
+    }
+
+    /// Top level keys and values, in the order of the file
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lines.iter().filter_map(|line| {
+            line.entry.as_ref().map(|(key, value)| (key.as_str(), value.as_str()))
+        })
+    }
+
+    /// Set the value in place of the existing one, or add it after the last top level line
+    pub fn set(&mut self, key: &str, value: &str) {
+        let text = format!("{}={}", key, format_value(value));
+        let entry = Some((key.to_string(), value.to_string()));
+        let indexes = self.find(key);
+        if let Some((last, others)) = indexes.split_last() {
+            self.lines[*last] = Line { text, entry };
+            for index in others.iter().rev() {
+                self.lines.remove(*index);
+            }
+            return;
+        }
+        // before the first section, and before the empty last line of a file ending with newline
+        let end = self.lines.iter().position(|line| is_section_header(&line.text));
+        let mut index = end.unwrap_or(self.lines.len());
+        while index > 0 && self.lines[index - 1].text.trim().is_empty() {
+            index -= 1;
+        }
+        if index == 0 && self.lines.len() == 1 && self.lines[0].text.is_empty() {
+            // an empty file
+            self.lines.insert(0, Line { text, entry });
+            return;
+        }
+        self.lines.insert(index, Line { text, entry });
+        if index == self.lines.len() - 1 {
+            self.lines.push(Line { text: String::new(), entry: None });
+        }
+    }
+
+    /// Remove the key, returns whether it existed
+    pub fn unset(&mut self, key: &str) -> bool {
+        let indexes = self.find(key);
+        for index in indexes.iter().rev() {
+            self.lines.remove(*index);
+        }
+        !indexes.is_empty()
+    }
+}
+
+impl Display for Npmrc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lines: Vec<&str> = self.lines.iter().map(|line| line.text.as_str()).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NPMRC: &str = "; registry of the company\nregistry = https://npm.corp/ # internal\n\
+                         strict-ssl\nsign-git-tag = \"a;b\"\n\n[section]\nregistry=ignored\n";
+
+    #[test]
+    fn parse_and_keep_format() {
+        let npmrc = Npmrc::parse(NPMRC);
+        assert_eq!(npmrc.to_string(), NPMRC);
+        assert_eq!(npmrc.get("registry"), Some("https://npm.corp/"));
+        assert_eq!(npmrc.get("strict-ssl"), Some("true"));
+        assert_eq!(npmrc.get("sign-git-tag"), Some("a;b"));
+        assert_eq!(npmrc.entries().count(), 3);
+    }
+
+    #[test]
+    fn set_and_unset_value() {
+        let mut npmrc = Npmrc::parse(NPMRC);
+        npmrc.set("registry", "https://registry.npmmirror.com/");
+        npmrc.set("proxy", "http://127.0.0.1:8080");
+        let expected = NPMRC
+            .replace(
+                "registry = https://npm.corp/ # internal",
+                "registry=https://registry.npmmirror.com/",
+            )
+            .replace("\"a;b\"\n", "\"a;b\"\nproxy=http://127.0.0.1:8080\n");
+        assert_eq!(npmrc.to_string(), expected);
+        assert!(npmrc.unset("proxy"));
+        assert!(!npmrc.unset("proxy"));
+
+        let mut npmrc = Npmrc::parse("");
+        npmrc.set("registry", "https://registry.npmjs.org/");
+        assert_eq!(npmrc.to_string(), "registry=https://registry.npmjs.org/\n");
+    }
+
+    #[test]
+    fn expand_env_vars() {
+        env::set_var("LANE_TEST_TOKEN", "secret");
+        assert_eq!(expand_env("${LANE_TEST_TOKEN}"), "secret");
+        assert_eq!(expand_env("a\\${LANE_TEST_TOKEN}b"), "a${LANE_TEST_TOKEN}b");
+        assert_eq!(expand_env("${LANE_TEST_UNKNOWN}"), "${LANE_TEST_UNKNOWN}");
+    }
+}
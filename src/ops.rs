@@ -0,0 +1,364 @@
+use crate::app::*;
+use crate::config::{self, Config, Profile};
+use crate::error::*;
+use crate::journal::{self, Snapshot};
+use log::warn;
+
+/// Result of an operation on a single app
+#[derive(Clone, Debug)]
+pub struct Outcome<T> {
+    pub app: &'static str,
+    pub result: Result<T, LaneError>,
+}
+
+/// Apps having the capability, narrowed down to the one named `selected` if any.
+///
+/// When no app is selected, apps not supporting `scope` are skipped as well.
+pub fn selected_apps(
+    capability: Capability,
+    selected: Option<&str>,
+    scope: Option<Scope>,
+) -> impl Iterator<Item = &'static dyn App> + '_ {
+    apps_with(capability).filter(move |app| match selected {
+        Some(name) => name == app.name(),
+        None => scope.is_none_or(|scope| app.supports_scope(scope)),
+    })
+}
+
+fn run<T>(
+    capability: Capability,
+    selected: Option<&str>,
+    scope: Option<Scope>,
+    op: impl Fn(&dyn App) -> Result<T, LaneError>,
+) -> Vec<Outcome<T>> {
+    selected_apps(capability, selected, scope)
+        .map(|app| Outcome { app: app.name(), result: op(app) })
+        .collect()
+}
+
+// run an operation modifying configs, and record the changes to the journal for undo
+fn run_recorded(
+    capability: Capability,
+    selected: Option<&str>,
+    scope: Option<Scope>,
+    op: impl Fn(&dyn App) -> Result<(), LaneError>,
+) -> Vec<Outcome<()>> {
+    let _lock = journal::lock();
+    let config_files = || {
+        selected_apps(capability, selected, scope)
+            .flat_map(|app| app.config_files(scope.unwrap_or(Scope::User)))
+    };
+    let mut snapshot = Snapshot::take(config_files());
+    let outcomes = run(capability, selected, scope, op);
+    snapshot.add_created(config_files());
+    journal::record(snapshot.changes());
+    outcomes
+}
+
+// scopes to query: the given one, or all scopes supported by the app
+fn scopes_of(app: &dyn App, scope: Option<Scope>) -> Result<Vec<Scope>, LaneError> {
+    match scope {
+        None => Ok(app.scopes().to_vec()),
+        Some(scope) if app.supports_scope(scope) => Ok(vec![scope]),
+        Some(scope) => Err(make_unsupported_scope_error(app.name(), scope)),
+    }
+}
+
+// scope to modify: the given one, or user scope by default
+fn scope_of(app: &dyn App, scope: Option<Scope>) -> Result<Scope, LaneError> {
+    let scope = scope.unwrap_or(Scope::User);
+    match app.supports_scope(scope) {
+        true => Ok(scope),
+        false => Err(make_unsupported_scope_error(app.name(), scope)),
+    }
+}
+
+/// Proxies of all scopes (or the given one), ordered by precedence
+pub fn get_proxies(app: Option<&str>, scope: Option<Scope>) -> Vec<Outcome<Vec<Proxy>>> {
+    run(Capability::Proxy, app, scope, |app| {
+        let mut proxies = vec![];
+        for scope in scopes_of(app, scope)? {
+            proxies.extend(app.get_proxies(scope)?);
+        }
+        Ok(proxies)
+    })
+}
+
+/// Set the proxy, and hosts accessed without it to apps supporting them. A named app not
+/// supporting them reports the error, while others only set the proxy.
+pub fn set_proxy(
+    app: Option<&str>,
+    scope: Option<Scope>,
+    proxy: &str,
+    no_proxy: &[String],
+) -> Vec<Outcome<()>> {
+    let named = app.is_some();
+    run_recorded(Capability::Proxy, app, scope, |app| {
+        let scope = scope_of(app, scope)?;
+        let set_no_proxy = !no_proxy.is_empty() && app.supports(Capability::NoProxy);
+        if !no_proxy.is_empty() && !set_no_proxy && named {
+            return Err(make_unsupported_error(app.name(), Capability::NoProxy));
+        }
+        app.set_proxy(scope, proxy)?;
+        match set_no_proxy {
+            true => app.set_no_proxy(scope, no_proxy),
+            false => Ok(()),
+        }
+    })
+}
+
+/// Unset the proxy, and hosts accessed without it of apps supporting them
+pub fn unset_proxy(app: Option<&str>, scope: Option<Scope>) -> Vec<Outcome<()>> {
+    run_recorded(Capability::Proxy, app, scope, |app| {
+        let scope = scope_of(app, scope)?;
+        let mut any_changed = changed(app.unset_proxy(scope))?;
+        if app.supports(Capability::NoProxy) {
+            any_changed |= changed(app.unset_no_proxy(scope))?;
+        }
+        any_changed.then_some(()).ok_or(LaneError::NothingToDo())
+    })
+}
+
+// a named app not supporting the narrower capability (like url proxies) reports the error, while
+// others are skipped
+fn narrowed(app: Option<&str>, capability: Capability, narrower: Capability) -> Capability {
+    match app {
+        Some(_) => capability,
+        None => narrower,
+    }
+}
+
+/// Set proxy for urls matching the pattern only, like `http.<url>.proxy` of git
+pub fn set_url_proxy(
+    app: Option<&str>,
+    scope: Option<Scope>,
+    url: &str,
+    proxy: &str,
+) -> Vec<Outcome<()>> {
+    let capability = narrowed(app, Capability::Proxy, Capability::UrlProxy);
+    run_recorded(capability, app, scope, |app| app.set_url_proxy(scope_of(app, scope)?, url, proxy))
+}
+
+/// Unset proxy of the url pattern, or proxies of all url patterns when `url` is `None`
+pub fn unset_url_proxy(
+    app: Option<&str>,
+    scope: Option<Scope>,
+    url: Option<&str>,
+) -> Vec<Outcome<()>> {
+    let capability = narrowed(app, Capability::Proxy, Capability::UrlProxy);
+    run_recorded(capability, app, scope, |app| app.unset_url_proxy(scope_of(app, scope)?, url))
+}
+
+/// Mirror in effect, i.e. the one of the scope with highest precedence (or the given scope)
+pub fn get_mirror(app: Option<&str>, scope: Option<Scope>) -> Vec<Outcome<Option<Mirror>>> {
+    run(Capability::Mirror, app, scope, |app| {
+        for scope in scopes_of(app, scope)? {
+            if let Some(mirror) = app.get_mirror(scope)? {
+                return Ok(Some(mirror));
+            }
+        }
+        Ok(None)
+    })
+}
+
+// the mirror found by name or url, with its auth token replaced by the given one if any
+fn resolve_mirror(
+    app: &dyn App,
+    mirror: &str,
+    auth_token: Option<&str>,
+) -> Result<MirrorEntry, LaneError> {
+    let mut mirror = app.resolve_mirror(mirror)?;
+    if let Some(token) = auth_token {
+        mirror.auth = Some(token.to_string());
+    }
+    Ok(mirror)
+}
+
+/// Set mirror of the app, `auth_token` overrides the one of the mirror in lane config
+pub fn set_mirror(
+    app: &str,
+    scope: Option<Scope>,
+    mirror: &str,
+    auth_token: Option<&str>,
+) -> Vec<Outcome<()>> {
+    run_recorded(Capability::Mirror, Some(app), scope, |app| {
+        app.set_mirror(scope_of(app, scope)?, &resolve_mirror(app, mirror, auth_token)?)
+    })
+}
+
+pub fn unset_mirror(app: Option<&str>, scope: Option<Scope>) -> Vec<Outcome<()>> {
+    run_recorded(Capability::Mirror, app, scope, |app| app.unset_mirror(scope_of(app, scope)?))
+}
+
+/// Mirrors of package scopes in all scopes (or the given one), ordered by precedence
+pub fn get_scoped_mirrors(app: Option<&str>, scope: Option<Scope>) -> Vec<Outcome<Vec<Mirror>>> {
+    let capability = narrowed(app, Capability::Mirror, Capability::ScopedMirror);
+    run(capability, app, scope, |app| {
+        let mut mirrors = vec![];
+        for scope in scopes_of(app, scope)? {
+            mirrors.extend(app.get_scoped_mirrors(scope)?);
+        }
+        Ok(mirrors)
+    })
+}
+
+/// Set mirror for packages of the scope only, like `@scope:registry` of npm
+pub fn set_scoped_mirror(
+    app: &str,
+    scope: Option<Scope>,
+    package_scope: &str,
+    mirror: &str,
+    auth_token: Option<&str>,
+) -> Vec<Outcome<()>> {
+    run_recorded(Capability::Mirror, Some(app), scope, |app| {
+        let mirror = resolve_mirror(app, mirror, auth_token)?;
+        app.set_scoped_mirror(scope_of(app, scope)?, package_scope, &mirror)
+    })
+}
+
+/// Unset mirror of the package scope, or mirrors of all package scopes when it's `None`
+pub fn unset_scoped_mirror(
+    app: Option<&str>,
+    scope: Option<Scope>,
+    package_scope: Option<&str>,
+) -> Vec<Outcome<()>> {
+    let capability = narrowed(app, Capability::Mirror, Capability::ScopedMirror);
+    run_recorded(capability, app, scope, |app| {
+        app.unset_scoped_mirror(scope_of(app, scope)?, package_scope)
+    })
+}
+
+/// Extra mirrors in all scopes (or the given one), ordered by precedence
+pub fn get_extra_mirrors(app: Option<&str>, scope: Option<Scope>) -> Vec<Outcome<Vec<Mirror>>> {
+    let capability = narrowed(app, Capability::Mirror, Capability::ExtraMirror);
+    run(capability, app, scope, |app| {
+        let mut mirrors = vec![];
+        for scope in scopes_of(app, scope)? {
+            mirrors.extend(app.get_extra_mirrors(scope)?);
+        }
+        Ok(mirrors)
+    })
+}
+
+/// Add a mirror searched besides the main one, like `extra-index-url` of pip
+pub fn add_extra_mirror(
+    app: &str,
+    scope: Option<Scope>,
+    mirror: &str,
+    auth_token: Option<&str>,
+) -> Vec<Outcome<()>> {
+    run_recorded(Capability::Mirror, Some(app), scope, |app| {
+        app.add_extra_mirror(scope_of(app, scope)?, &resolve_mirror(app, mirror, auth_token)?)
+    })
+}
+
+/// Remove the extra mirror, or all extra mirrors when it's `None`
+pub fn unset_extra_mirror(
+    app: Option<&str>,
+    scope: Option<Scope>,
+    mirror: Option<&str>,
+) -> Vec<Outcome<()>> {
+    let capability = narrowed(app, Capability::Mirror, Capability::ExtraMirror);
+    run_recorded(capability, app, scope, |app| {
+        let mirror = mirror.map(|mirror| app.resolve_mirror(mirror)).transpose()?;
+        app.unset_extra_mirror(scope_of(app, scope)?, mirror.as_ref())
+    })
+}
+
+// whether an operation changed anything, "nothing to do" is not a failure in a batch
+fn changed(result: Result<(), LaneError>) -> Result<bool, LaneError> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(LaneError::NothingToDo()) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+fn apply_profile(app: &dyn App, scope: Scope, profile: &Profile) -> Result<(), LaneError> {
+    let mut any_changed = false;
+    if app.supports(Capability::Proxy) {
+        any_changed |= match &profile.proxy {
+            Some(proxy) => changed(app.set_proxy(scope, proxy))?,
+            None if app.get_proxies(scope)?.is_empty() => false,
+            None => changed(app.unset_proxy(scope))?,
+        };
+    }
+    if app.supports(Capability::NoProxy) {
+        any_changed |= match profile.no_proxy.is_empty() {
+            true => changed(app.unset_no_proxy(scope))?,
+            false => changed(app.set_no_proxy(scope, &profile.no_proxy))?,
+        };
+    }
+    if let Some(mirror) = profile.mirrors.get(app.name()) {
+        any_changed |= changed(app.set_mirror(scope, &app.resolve_mirror(mirror)?))?;
+    }
+    any_changed.then_some(()).ok_or(LaneError::NothingToDo())
+}
+
+/// Apply the profile in lane config to all apps: proxy and no-proxy list replace the current ones
+/// (or are cleared when missing), and mirrors are set for the apps listed only
+pub fn use_profile(name: &str, scope: Option<Scope>) -> Result<Vec<Outcome<()>>, LaneError> {
+    let config = Config::load()?;
+    let profile = config.profile(name)?;
+    for app in profile.mirrors.keys() {
+        if !app_names_with(Capability::Mirror).contains(&app.as_str()) {
+            warn!("Profile {} has mirror of unknown app {}, ignored", name, app);
+        }
+    }
+    let selected: Vec<&dyn App> = apps()
+        .iter()
+        .copied()
+        .filter(|app| app.supports(Capability::Proxy) || profile.mirrors.contains_key(app.name()))
+        .filter(|app| scope.is_none_or(|scope| app.supports_scope(scope)))
+        .collect();
+    let _lock = journal::lock();
+    let config_files =
+        || selected.iter().flat_map(|app| app.config_files(scope.unwrap_or(Scope::User)));
+    let mut snapshot = Snapshot::take(config_files());
+    let outcomes = selected
+        .iter()
+        .map(|&app| Outcome {
+            app: app.name(),
+            result: scope_of(app, scope).and_then(|scope| apply_profile(app, scope, profile)),
+        })
+        .collect();
+    snapshot.add_created(config_files());
+    journal::record(snapshot.changes());
+    Ok(outcomes)
+}
+
+/// Check the profile and save it to lane config
+pub fn add_profile(name: &str, profile: &Profile) -> Result<(), LaneError> {
+    if let Some(proxy) = &profile.proxy {
+        validate_proxy_url(proxy)?;
+    }
+    for (app_name, mirror) in &profile.mirrors {
+        match find_app(app_name).filter(|app| app.supports(Capability::Mirror)) {
+            Some(app) => app.resolve_mirror(mirror).map(|_| ())?,
+            None => return Err(make_unsupported_error(app_name, Capability::Mirror)),
+        }
+    }
+    config::save_profile(name, profile)
+}
+
+/// Counts of outcomes of an operation
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub nothing_to_do: usize,
+}
+
+impl Summary {
+    pub fn of<T>(outcomes: &[Outcome<T>]) -> Self {
+        let mut summary = Summary::default();
+        for outcome in outcomes {
+            match &outcome.result {
+                Ok(_) => summary.succeeded += 1,
+                Err(LaneError::NothingToDo()) => summary.nothing_to_do += 1,
+                Err(_) => summary.failed += 1,
+            }
+        }
+        summary
+    }
+}
@@ -0,0 +1,257 @@
+// A format preserving editor of pip config files, in the ini format of python configparser:
+// https://pip.pypa.io/en/stable/topics/configuration/
+//
+// Lines not modified are kept as is, including comments. Values continue on indented lines, as
+// lists like `extra-index-url` are usually written. Keys are compared as pip does, ignoring case
+// and taking '_' as '-'.
+
+use std::fmt::Display;
+
+#[derive(Clone, Debug)]
+struct Line {
+    /// Text of the line, several physical lines for a value continued on indented lines
+    text: String,
+    /// Name of the section started by the line
+    header: Option<String>,
+    /// Normalized key and value of the line
+    entry: Option<(String, String)>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PipConf {
+    lines: Vec<Line>,
+}
+
+fn normalize_key(key: &str) -> String {
+    key.trim().to_lowercase().replace('_', "-")
+}
+
+fn parse_header(line: &str) -> Option<String> {
+    let name = line.trim_end().strip_prefix('[')?.strip_suffix(']')?;
+    Some(name.to_string())
+}
+
+fn is_comment(line: &str) -> bool {
+    line.trim_start().starts_with(['#', ';'])
+}
+
+// "key = value" or "key: value", split at the first delimiter
+fn parse_entry(line: &str) -> Option<(String, String)> {
+    if line.trim().is_empty() || is_comment(line) || line.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let index = line.find(['=', ':'])?;
+    Some((normalize_key(&line[..index]), line[index + 1..].trim().to_string()))
+}
+
+// lines of a multi-line value are indented under the key
+fn format_entry(key: &str, value: &str) -> String {
+    let lines: Vec<&str> = value.split('\n').map(str::trim).collect();
+    format!("{} = {}", key, lines.join("\n    "))
+}
+
+impl PipConf {
+    pub fn parse(content: &str) -> Self {
+        let mut lines: Vec<Line> = vec![];
+        // empty lines after an entry, which belong to it if the value continues after them
+        let mut pending: Vec<&str> = vec![];
+        for text in content.split('\n') {
+            let last_entry = lines.last_mut().filter(|line| line.entry.is_some());
+            let continued = text.starts_with(char::is_whitespace) && !text.trim().is_empty();
+            match last_entry {
+                Some(line) if continued && !is_comment(text) => {
+                    for empty in pending.drain(..) {
+                        line.text += &format!("\n{}", empty);
+                    }
+                    line.text += &format!("\n{}", text);
+                    if let Some((_, value)) = &mut line.entry {
+                        value.push('\n');
+                        value.push_str(text.trim());
+                        *value = value.trim().to_string();
+                    }
+                    continue;
+                }
+                Some(_) if text.trim().is_empty() => {
+                    pending.push(text);
+                    continue;
+                }
+                _ => {}
+            }
+            for empty in pending.drain(..) {
+                lines.push(Line { text: empty.to_string(), header: None, entry: None });
+            }
+            let header = parse_header(text);
+            let entry = if header.is_some() { None } else { parse_entry(text) };
+            lines.push(Line { text: text.to_string(), header, entry });
+        }
+        for empty in pending {
+            lines.push(Line { text: empty.to_string(), header: None, entry: None });
+        }
+        PipConf { lines }
+    }
+
+    // name of the section of every line, `None` for lines before the first section
+    fn sections(&self) -> Vec<Option<&str>> {
+        let mut section = None;
+        let mut sections = vec![];
+        for line in &self.lines {
+            if let Some(name) = &line.header {
+                section = Some(name.as_str());
+            }
+            sections.push(section);
+        }
+        sections
+    }
+
+    // indexes of lines with the key in the section
+    fn find(&self, section: &str, key: &str) -> Vec<usize> {
+        let key = normalize_key(key);
+        let sections = self.sections();
+        let matches = |i: usize| {
+            let line = &self.lines[i];
+            sections[i] == Some(section) && line.entry.as_ref().is_some_and(|(k, _)| *k == key)
+        };
+        (0..self.lines.len()).filter(|i| matches(*i)).collect()
+    }
+
+    /// Value of the key in the section, the last one wins
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        let index = *self.find(section, key).last()?;
+        self.lines[index].entry.as_ref().map(|(_, value)| value.as_str())
+    }
+
+    /// Sections having the key
+    pub fn sections_of(&self, key: &str) -> Vec<&str> {
+        let key = normalize_key(key);
+        let mut result = vec![];
+        for (line, section) in self.lines.iter().zip(self.sections()) {
+            let has_key = line.entry.as_ref().is_some_and(|(k, _)| *k == key);
+            if let (true, Some(section)) = (has_key, section) {
+                if !result.contains(&section) {
+                    result.push(section);
+                }
+            }
+        }
+        result
+    }
+
+    /// Set the value in place of the existing one, or add it to the end of the section, which is
+    /// created when missing. Lines of a multi-line value are separated by '\n'.
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        let line = Line {
+            text: format_entry(key, value),
+            header: None,
+            entry: Some((normalize_key(key), value.to_string())),
+        };
+        let indexes = self.find(section, key);
+        if let Some((last, others)) = indexes.split_last() {
+            self.lines[*last] = line;
+            for index in others.iter().rev() {
+                self.lines.remove(*index);
+            }
+            return;
+        }
+        let sections = self.sections();
+        match sections.iter().rposition(|s| *s == Some(section)) {
+            Some(mut index) => {
+                // after the last non-empty line of the section
+                while self.lines[index].text.trim().is_empty() {
+                    index -= 1;
+                }
+                self.lines.insert(index + 1, line);
+            }
+            None => {
+                // a new section at the end, separated from the former one by an empty line
+                while self.lines.last().is_some_and(|line| line.text.trim().is_empty()) {
+                    self.lines.pop();
+                }
+                if !self.lines.is_empty() {
+                    self.lines.push(Line { text: String::new(), header: None, entry: None });
+                }
+                let header = Some(section.to_string());
+                self.lines.push(Line { text: format!("[{}]", section), header, entry: None });
+                self.lines.push(line);
+                self.lines.push(Line { text: String::new(), header: None, entry: None });
+            }
+        }
+    }
+
+    /// Remove the key from the section, and the section if nothing is left in it. Returns whether
+    /// the key existed.
+    pub fn unset(&mut self, section: &str, key: &str) -> bool {
+        let indexes = self.find(section, key);
+        for index in indexes.iter().rev() {
+            self.lines.remove(*index);
+        }
+        if indexes.is_empty() {
+            return false;
+        }
+        // headers of the section left with empty lines only
+        let empty_sections: Vec<(usize, usize)> = (0..self.lines.len())
+            .filter(|i| self.lines[*i].header.as_deref() == Some(section))
+            .map(|header| {
+                let rest = &self.lines[header + 1..];
+                (header, rest.iter().take_while(|line| line.header.is_none()).count())
+            })
+            .filter(|(header, len)| {
+                self.lines[header + 1..=header + len].iter().all(|line| line.text.trim().is_empty())
+            })
+            .collect();
+        for (header, len) in empty_sections.into_iter().rev() {
+            self.lines.drain(header..=header + len);
+        }
+        true
+    }
+}
+
+impl Display for PipConf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lines: Vec<&str> = self.lines.iter().map(|line| line.text.as_str()).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIP_CONF: &str = "# pip\n[global]\ntimeout = 60\nIndex_URL: https://pypi.corp/simple\n\
+                            extra-index-url =\n    https://a.corp/simple\n\n    https://b.corp/simple\n\
+                            \n[install]\ntrusted-host = pypi.corp\n";
+
+    #[test]
+    fn parse_and_keep_format() {
+        let config = PipConf::parse(PIP_CONF);
+        assert_eq!(config.to_string(), PIP_CONF);
+        assert_eq!(config.get("global", "index-url"), Some("https://pypi.corp/simple"));
+        assert_eq!(
+            config.get("global", "extra_index_url"),
+            Some("https://a.corp/simple\nhttps://b.corp/simple")
+        );
+        assert_eq!(config.get("global", "trusted-host"), None);
+        assert_eq!(config.sections_of("trusted-host"), ["install"]);
+    }
+
+    #[test]
+    fn set_and_unset_value() {
+        let mut config = PipConf::parse(PIP_CONF);
+        config.set("global", "index-url", "https://pypi.tuna.tsinghua.edu.cn/simple");
+        config.set("global", "extra-index-url", "https://a.corp/simple");
+        config.set("global", "proxy", "http://127.0.0.1:8080");
+        let expected =
+            "# pip\n[global]\ntimeout = 60\nindex-url = https://pypi.tuna.tsinghua.edu.cn/simple\n\
+                        extra-index-url = https://a.corp/simple\nproxy = http://127.0.0.1:8080\n\
+                        \n[install]\ntrusted-host = pypi.corp\n";
+        assert_eq!(config.to_string(), expected);
+        assert!(config.unset("install", "trusted-host"));
+        assert!(!config.unset("install", "trusted-host"));
+        let expected = expected.replace("\n[install]\ntrusted-host = pypi.corp\n", "");
+        assert_eq!(config.to_string(), expected);
+
+        let mut config = PipConf::parse("");
+        config.set("global", "trusted-host", "a.corp\nb.corp");
+        assert_eq!(config.to_string(), "[global]\ntrusted-host = a.corp\n    b.corp\n");
+        assert!(config.unset("global", "trusted-host"));
+        assert_eq!(config.to_string(), "");
+    }
+}
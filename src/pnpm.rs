@@ -0,0 +1,129 @@
+// pnpm: https://pnpm.io/npmrc
+//
+// pnpm reads the same keys as npm, from .npmrc of the project, and its own global config besides
+// the user config of npm.
+
+use dirs::home_dir;
+use std::{env, path::PathBuf};
+
+use crate::{
+    app::*,
+    error::*,
+    npm::{self, get_project_dir, get_user_config_path, NpmrcFiles, NPM_CONFIG},
+};
+
+const PNPM_CONFIG: &str = "pnpm/rc";
+#[cfg(all(unix, not(target_os = "macos")))]
+const DEFAULT_CONFIG_HOME: &str = ".config";
+#[cfg(target_os = "macos")]
+const DEFAULT_CONFIG_HOME: &str = "Library/Preferences";
+
+#[cfg(not(target_os = "windows"))]
+fn get_default_config_path() -> Result<PathBuf, LaneError> {
+    Ok(home_dir().ok_or(LaneError::NoHomeDir())?.join(DEFAULT_CONFIG_HOME).join(PNPM_CONFIG))
+}
+
+#[cfg(target_os = "windows")]
+fn get_default_config_path() -> Result<PathBuf, LaneError> {
+    let dir = dirs::data_local_dir().ok_or(LaneError::NoHomeDir())?;
+    Ok(dir.join("pnpm").join("config").join("rc"))
+}
+
+// global config of pnpm, written by "pnpm config set"
+fn get_global_config_path() -> Result<PathBuf, LaneError> {
+    match env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        Some(dir) => Ok(PathBuf::from(dir).join(PNPM_CONFIG)),
+        None => get_default_config_path(),
+    }
+}
+
+fn get_config_files(scope: Scope) -> Result<NpmrcFiles, LaneError> {
+    match scope {
+        Scope::Project => {
+            let path = get_project_dir()?.join(NPM_CONFIG);
+            Ok(NpmrcFiles { scope, path: Some(path.clone()), files: vec![path] })
+        }
+        _ => {
+            let path = get_global_config_path()?;
+            let files = vec![path.clone(), get_user_config_path()?];
+            Ok(NpmrcFiles { scope, path: Some(path), files })
+        }
+    }
+}
+
+pub struct Pnpm;
+
+impl App for Pnpm {
+    fn name(&self) -> &'static str {
+        "pnpm"
+    }
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::Proxy, Capability::NoProxy, Capability::Mirror, Capability::ScopedMirror]
+    }
+
+    fn scopes(&self) -> &'static [Scope] {
+        &[Scope::Project, Scope::User]
+    }
+
+    fn config_files(&self, scope: Scope) -> Vec<PathBuf> {
+        get_config_files(scope).ok().and_then(|files| files.path).into_iter().collect()
+    }
+
+    fn get_proxies(&self, scope: Scope) -> Result<Vec<Proxy>, LaneError> {
+        get_config_files(scope)?.get_proxies()
+    }
+
+    fn set_proxy(&self, scope: Scope, proxy: &str) -> Result<(), LaneError> {
+        get_config_files(scope)?.set_proxy(proxy)
+    }
+
+    fn unset_proxy(&self, scope: Scope) -> Result<(), LaneError> {
+        get_config_files(scope)?.unset_proxy()
+    }
+
+    fn set_no_proxy(&self, scope: Scope, hosts: &[String]) -> Result<(), LaneError> {
+        get_config_files(scope)?.set_no_proxy(hosts)
+    }
+
+    fn unset_no_proxy(&self, scope: Scope) -> Result<(), LaneError> {
+        get_config_files(scope)?.unset_no_proxy()
+    }
+
+    fn builtin_mirrors(&self) -> Vec<MirrorEntry> {
+        mirror_entries::<npm::Mirrors>()
+    }
+
+    fn get_mirror(&self, scope: Scope) -> Result<Option<Mirror>, LaneError> {
+        get_config_files(scope)?.get_mirror(self)
+    }
+
+    fn set_mirror(&self, scope: Scope, mirror: &MirrorEntry) -> Result<(), LaneError> {
+        get_config_files(scope)?.set_mirror(mirror)
+    }
+
+    fn unset_mirror(&self, scope: Scope) -> Result<(), LaneError> {
+        get_config_files(scope)?.unset_mirror()
+    }
+
+    fn get_scoped_mirrors(&self, scope: Scope) -> Result<Vec<Mirror>, LaneError> {
+        get_config_files(scope)?.get_scoped_mirrors(self)
+    }
+
+    fn set_scoped_mirror(
+        &self,
+        scope: Scope,
+        package_scope: &str,
+        mirror: &MirrorEntry,
+    ) -> Result<(), LaneError> {
+        get_config_files(scope)?.set_scoped_mirror(package_scope, mirror)
+    }
+
+    fn unset_scoped_mirror(
+        &self,
+        scope: Scope,
+        package_scope: Option<&str>,
+    ) -> Result<(), LaneError> {
+        get_config_files(scope)?.unset_scoped_mirror(package_scope)
+    }
+}
@@ -0,0 +1,284 @@
+// Proxies of ssh hosts in ssh config, see `man ssh_config`.
+//
+// lane only edits Host blocks between its own markers, which are placed at the top of the file as
+// ssh uses the first value obtained for each option. The block ends with `Host *`, so that options
+// following it in the file apply to all hosts again. The rest of the file is kept as is.
+
+use std::fmt::Display;
+use url::Url;
+
+const BEGIN_MARKER: &str = "# >>> hosts managed by lane >>>";
+const END_MARKER: &str = "# <<< hosts managed by lane <<<";
+// closes the last Host block of lane
+const ALL_HOSTS: &str = "Host *";
+pub const KEY_PROXY_COMMAND: &str = "ProxyCommand";
+pub const KEY_PROXY_JUMP: &str = "ProxyJump";
+
+/// Proxy option of a host
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HostProxy {
+    pub host: String,
+    /// `ProxyCommand` or `ProxyJump`
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct SshConfig {
+    /// Content before lane's block, or the whole file when the block doesn't exist
+    before: String,
+    hosts: Vec<HostProxy>,
+    after: String,
+    has_block: bool,
+}
+
+// split content at the marker line, returning content before the line and after it
+fn split_at_marker<'a>(content: &'a str, marker: &str) -> Option<(&'a str, &'a str)> {
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        if line.trim() == marker {
+            return Some((&content[..offset], &content[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+impl SshConfig {
+    /// Parse ssh config, `None` when lane's block is broken
+    pub fn parse(content: &str) -> Option<Self> {
+        let (before, rest) = match split_at_marker(content, BEGIN_MARKER) {
+            Some(split) => split,
+            None => {
+                let config = SshConfig {
+                    before: String::new(),
+                    hosts: vec![],
+                    after: content.to_string(),
+                    has_block: false,
+                };
+                return Some(config);
+            }
+        };
+        let (block, after) = split_at_marker(rest, END_MARKER)?;
+        let mut hosts: Vec<HostProxy> = vec![];
+        let mut current_host = None;
+        for line in block.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once([' ', '\t', '='])?;
+            let value = value.trim_start_matches([' ', '\t', '=']).to_string();
+            if line == ALL_HOSTS {
+                current_host = None;
+            } else if key.eq_ignore_ascii_case("Host") {
+                current_host = Some(value);
+            } else {
+                hosts.push(HostProxy { host: current_host.clone()?, key: key.to_string(), value });
+            }
+        }
+        Some(SshConfig {
+            before: before.to_string(),
+            hosts,
+            after: after.to_string(),
+            has_block: true,
+        })
+    }
+
+    pub fn hosts(&self) -> &[HostProxy] {
+        &self.hosts
+    }
+
+    pub fn set(&mut self, host: &str, key: &str, value: &str) {
+        let proxy =
+            HostProxy { host: host.to_string(), key: key.to_string(), value: value.to_string() };
+        match self.hosts.iter_mut().find(|h| h.host == host) {
+            Some(existed) => *existed = proxy,
+            None => self.hosts.push(proxy),
+        }
+        if !self.has_block {
+            // keep lane's block apart from the rest
+            self.has_block = true;
+            if !self.after.is_empty() {
+                self.after.insert(0, '\n');
+            }
+        }
+    }
+
+    /// Remove proxy of the host, returns whether it existed
+    pub fn unset(&mut self, host: &str) -> bool {
+        let count = self.hosts.len();
+        self.hosts.retain(|h| h.host != host);
+        if self.hosts.is_empty() && self.has_block {
+            self.has_block = false;
+            if self.before.is_empty() && self.after.starts_with('\n') {
+                self.after.remove(0);
+            }
+        }
+        self.hosts.len() != count
+    }
+}
+
+impl Display for SshConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.before)?;
+        if self.has_block {
+            writeln!(f, "{}", BEGIN_MARKER)?;
+            for proxy in &self.hosts {
+                writeln!(f, "Host {}", proxy.host)?;
+                writeln!(f, "    {} {}", proxy.key, proxy.value)?;
+            }
+            writeln!(f, "{}", ALL_HOSTS)?;
+            writeln!(f, "{}", END_MARKER)?;
+        }
+        write!(f, "{}", self.after)
+    }
+}
+
+// host and port of the proxy, `None` for a host with chars other than [A-Za-z0-9.:-], as commands
+// containing it are run by shell
+fn proxy_address(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || ".:-".contains(c);
+    if !host.chars().all(is_safe) {
+        return None;
+    }
+    match url.port() {
+        Some(port) => Some(format!("{}:{}", host, port)),
+        None => Some(host.to_string()),
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn netcat_command(kind: &str, address: &str, host: &str, port: &str) -> String {
+    // netcat of OpenBSD, the default one of macOS and most linux distributions
+    format!("nc -X {} -x {} {} {}", kind, address, host, port)
+}
+
+#[cfg(target_os = "windows")]
+fn netcat_command(kind: &str, address: &str, host: &str, port: &str) -> String {
+    // connect.exe shipped with git for windows
+    let option = match kind {
+        "connect" => "-H",
+        _ => "-S",
+    };
+    format!("connect {} {} {} {}", option, address, host, port)
+}
+
+/// Command connecting to `host` and `port` through a socks or http proxy, `None` for other proxies
+pub fn proxy_command(proxy: &str, host: &str, port: &str) -> Option<String> {
+    let url = Url::parse(proxy).ok()?;
+    let kind = match url.scheme() {
+        "socks5" | "socks5h" => "5",
+        "socks4" | "socks4a" => "4",
+        "http" => "connect",
+        _ => return None,
+    };
+    Some(netcat_command(kind, &proxy_address(&url)?, host, port))
+}
+
+/// Option of ssh config to connect through the proxy, `None` for unsupported proxies
+pub fn proxy_option(proxy: &str) -> Option<(&'static str, String)> {
+    let url = Url::parse(proxy).ok()?;
+    if url.scheme() == "ssh" {
+        let jump = match url.username() {
+            "" => proxy_address(&url)?,
+            user => format!("{}@{}", user, proxy_address(&url)?),
+        };
+        return Some((KEY_PROXY_JUMP, jump));
+    }
+    Some((KEY_PROXY_COMMAND, proxy_command(proxy, "%h", "%p")?))
+}
+
+/// Proxy url of an option generated by `proxy_option()`, or the raw option for others
+pub fn proxy_of_option(key: &str, value: &str) -> String {
+    if key.eq_ignore_ascii_case(KEY_PROXY_JUMP) {
+        return format!("ssh://{}", value);
+    }
+    let args: Vec<&str> = value.split_whitespace().collect();
+    let proxy = match args.as_slice() {
+        ["nc", "-X", kind, "-x", address, "%h", "%p"] => match *kind {
+            "5" => Some(format!("socks5://{}", address)),
+            "4" => Some(format!("socks4://{}", address)),
+            "connect" => Some(format!("http://{}", address)),
+            _ => None,
+        },
+        ["connect", "-S", address, "%h", "%p"] => Some(format!("socks5://{}", address)),
+        ["connect", "-H", address, "%h", "%p"] => Some(format!("http://{}", address)),
+        _ => None,
+    };
+    proxy.unwrap_or_else(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "Host *\n    ServerAliveInterval 60\n";
+
+    #[test]
+    fn set_and_unset_host() {
+        let mut config = SshConfig::parse(CONFIG).unwrap();
+        config.set("github.com", KEY_PROXY_COMMAND, "nc -X 5 -x 127.0.0.1:1080 %h %p");
+        config.set("gitlab.com", KEY_PROXY_JUMP, "jump.corp");
+        let expected = format!(
+            "{}\nHost github.com\n    ProxyCommand nc -X 5 -x 127.0.0.1:1080 %h %p\n\
+             Host gitlab.com\n    ProxyJump jump.corp\nHost *\n{}\n\n{}",
+            BEGIN_MARKER, END_MARKER, CONFIG
+        );
+        assert_eq!(config.to_string(), expected);
+
+        let mut config = SshConfig::parse(&expected).unwrap();
+        assert_eq!(config.hosts().len(), 2);
+        assert!(config.unset("github.com"));
+        assert!(!config.unset("github.com"));
+        assert!(config.unset("gitlab.com"));
+        assert_eq!(config.to_string(), CONFIG);
+    }
+
+    // options at the top level of the file must not become options of lane's last host
+    #[test]
+    fn close_block_before_top_level_options() {
+        let content =
+            "Include ~/.ssh/config.d/*\nServerAliveInterval 60\n\nHost corp\n    User me\n";
+        let mut config = SshConfig::parse(content).unwrap();
+        config.set("github.com", KEY_PROXY_JUMP, "jump.corp");
+        let expected = format!(
+            "{}\nHost github.com\n    ProxyJump jump.corp\nHost *\n{}\n\n{}",
+            BEGIN_MARKER, END_MARKER, content
+        );
+        assert_eq!(config.to_string(), expected);
+
+        let mut config = SshConfig::parse(&expected).unwrap();
+        assert_eq!(config.hosts().len(), 1);
+        config.set("gitlab.com", KEY_PROXY_JUMP, "jump.corp");
+        assert!(config.to_string().contains("gitlab.com\n    ProxyJump jump.corp\nHost *\n"));
+        assert!(config.unset("github.com") && config.unset("gitlab.com"));
+        assert_eq!(config.to_string(), content);
+    }
+
+    #[test]
+    fn broken_block() {
+        assert!(SshConfig::parse(&format!("{}\nHost a\n", BEGIN_MARKER)).is_none());
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn convert_proxy() {
+        for proxy in ["socks5://127.0.0.1:1080", "http://proxy:8080", "ssh://me@jump:2222"] {
+            let (key, value) = proxy_option(proxy).unwrap();
+            assert_eq!(proxy_of_option(key, &value), proxy);
+        }
+        assert_eq!(proxy_option("ftp://a"), None);
+    }
+
+    #[test]
+    fn reject_hosts_run_by_shell() {
+        for proxy in ["socks5://a;id:1080", "socks5://a$(id):1080", "socks5://a`id`&:1080"] {
+            assert!(Url::parse(proxy).is_ok());
+            assert_eq!(proxy_command(proxy, "\"$1\"", "\"$2\""), None);
+            assert_eq!(proxy_option(proxy), None);
+        }
+        assert!(proxy_command("socks5://proxy-1.corp:1080", "\"$1\"", "\"$2\"").is_some());
+    }
+}
@@ -1,27 +1,167 @@
-use std::{ffi::OsStr, process::Command, str::from_utf8};
+use crate::dry_run;
 use crate::error::*;
+use std::{
+    fs::{self, create_dir_all, remove_file, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+};
+use toml_edit::{Document, Item, Table, TableLike, Value};
 
-pub fn exec<S, I, S2>(cmd: S, args: I) -> bool
-where
-    S: AsRef<OsStr>,
-    I: IntoIterator<Item = S2>,
-    S2: AsRef<OsStr>,
-{
-    if let Ok(status) = Command::new(cmd).args(args).status() {
-        return status.success();
-    }
-    false
-}
-
-pub fn exec2<S, I, S2>(cmd: S, args: I) -> Result<String, LaneError>
-where
-    S: AsRef<OsStr>,
-    I: IntoIterator<Item = S2>,
-    S2: AsRef<OsStr>,
-{
-    let output = Command::new(cmd).args(args).output().map_err(make_command_failed_error)?;
-    // if output.status.success() {
-    //     return Err(LaneError::CommandFailed());
-    // }
-    from_utf8(&output.stdout).map(|s| s.to_string()).map_err(make_command_failed_error)
+/// Read a config file, like `fs::read_to_string()` but returns planned content in a dry run
+pub fn read_file(path: &Path) -> io::Result<String> {
+    match dry_run::planned_content(path) {
+        Some(Some(content)) => Ok(content),
+        Some(None) => Err(io::ErrorKind::NotFound.into()),
+        None => fs::read_to_string(path),
+    }
+}
+
+// the file a symlink points to, so dotfiles linked from elsewhere are updated in place
+fn resolve_symlinks(path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+    // limit the depth in case of a symlink loop
+    for _ in 0..40 {
+        match fs::read_link(&path) {
+            Ok(target) => path = path.parent().unwrap_or(Path::new("")).join(target),
+            Err(_) => break,
+        }
+    }
+    path
+}
+
+/// Write a config file, like `fs::write()` but only plans the change in a dry run.
+///
+/// Content is written to a temp file in the same dir first, then renamed to the target, so the file
+/// is never left truncated. Permissions of an existed file are kept.
+pub fn write_file(path: &Path, content: &str) -> io::Result<()> {
+    if dry_run::is_active() {
+        dry_run::plan_file(path.to_path_buf(), read_file(path).ok(), Some(content.to_string()));
+        return Ok(());
+    }
+    let path = resolve_symlinks(path);
+    let name = path.file_name().ok_or(io::ErrorKind::InvalidInput)?.to_string_lossy();
+    let temp = path.with_file_name(format!(".{}.lane-{}", name, process::id()));
+    let mut file = match OpenOptions::new().write(true).create_new(true).open(&temp) {
+        Ok(file) => file,
+        // a writable file in a dir not writable, like a shared config in /etc, is written in place
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied && path.is_file() => {
+            return fs::write(&path, content);
+        }
+        Err(e) => return Err(e),
+    };
+    let result = (|| {
+        if let Ok(metadata) = fs::metadata(&path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp, &path)
+    })();
+    if result.is_err() {
+        let _ = remove_file(&temp);
+    }
+    result
+}
+
+pub fn read_document(path: &Path) -> Result<Document, LaneError> {
+    let content = read_file(path).map_err(|_| make_invalid_file_error(path))?;
+    content.parse().map_err(|_| make_invalid_file_error(path))
+}
+
+/// Write a config file, creating its dir if needed
+pub fn write_config_file(path: &Path, content: &str) -> Result<(), LaneError> {
+    if let Some(dir) = path.parent().filter(|_| !dry_run::is_active()) {
+        create_dir_all(dir).map_err(|e| make_write_file_error(path, &e))?;
+    }
+    write_file(path, content).map_err(|e| make_write_file_error(path, &e))
+}
+
+/// Remove a config file, like `fs::remove_file()` but only plans the change in a dry run. A missing
+/// file is removed already.
+pub fn remove_config_file(path: &Path) -> Result<(), LaneError> {
+    if dry_run::is_active() {
+        if let Ok(content) = read_file(path) {
+            dry_run::plan_file(path.to_path_buf(), Some(content), None);
+        }
+        return Ok(());
+    }
+    match remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(make_write_file_error(path, &e)),
+        _ => Ok(()),
+    }
+}
+
+/// Make a script written by `write_file()` executable
+pub fn make_executable(path: &Path) -> Result<(), LaneError> {
+    if dry_run::is_active() {
+        return Ok(());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+            .map_err(|e| make_write_file_error(path, &e))?;
+    }
+    Ok(())
+}
+
+// get a table, or create it as implicit one when not exists
+pub fn get_or_insert_table<'a>(
+    parent: &'a mut dyn TableLike,
+    key: &str,
+) -> Result<&'a mut dyn TableLike, LaneError> {
+    parent
+        .entry(key)
+        .or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        })
+        .as_table_like_mut()
+        .ok_or_else(|| make_failure_error(format!("Invalid {} section.", key)))
+}
+
+// set a string value, keeping the decoration (spaces and comments) of the existed value
+pub fn set_string(table: &mut dyn TableLike, key: &str, value: &str) {
+    match table.get_mut(key).and_then(|item| item.as_value_mut()) {
+        Some(existed) => {
+            let decor = existed.decor().clone();
+            *existed = Value::from(value);
+            *existed.decor_mut() = decor;
+        }
+        None => {
+            table.insert(key, toml_edit::value(value));
+        }
+    }
+}
+
+pub fn write_document(path: &Path, document: &Document) -> Result<(), LaneError> {
+    write_config_file(path, &document.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    #[cfg(unix)]
+    #[test]
+    fn write_file_through_symlink() {
+        let dir = std::env::temp_dir().join(format!("lane-test-{}", process::id()));
+        create_dir_all(&dir).unwrap();
+        let target = dir.join("dotfile");
+        let link = dir.join("link");
+        fs::write(&target, "old").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+        symlink("dotfile", &link).unwrap();
+
+        write_file(&link, "new").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert_eq!(fs::metadata(&target).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2); // no temp file left
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
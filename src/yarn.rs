@@ -0,0 +1,281 @@
+// yarn: classic https://classic.yarnpkg.com/en/docs/yarnrc, berry https://yarnpkg.com/configuration/yarnrc
+//
+// Both config files of a scope are managed, as the version of yarn in use depends on the project.
+// When none of them exists, the one of the version used by the current project is created.
+
+use dirs::home_dir;
+use log::warn;
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    app::*,
+    error::*,
+    npm::{self, get_project_dir},
+    utils::*,
+    yarnrc::{Format, Yarnrc},
+};
+
+const CLASSIC_CONFIG: &str = ".yarnrc";
+const BERRY_CONFIG: &str = ".yarnrc.yml";
+const PACKAGE_JSON: &str = "package.json";
+const LOCKFILE: &str = "yarn.lock";
+
+/// Keys of a config format
+struct Keys {
+    registry: &'static str,
+    proxy: &'static str,
+    https_proxy: &'static str,
+    /// Token of the registry, `None` for classic yarn, which reads tokens from `.npmrc` only
+    auth_token: Option<&'static str>,
+}
+
+const CLASSIC_KEYS: Keys =
+    Keys { registry: "registry", proxy: "proxy", https_proxy: "https-proxy", auth_token: None };
+const BERRY_KEYS: Keys = Keys {
+    registry: "npmRegistryServer",
+    proxy: "httpProxy",
+    https_proxy: "httpsProxy",
+    auth_token: Some("npmAuthToken"),
+};
+
+fn keys_of(format: Format) -> &'static Keys {
+    match format {
+        Format::Classic => &CLASSIC_KEYS,
+        Format::Berry => &BERRY_KEYS,
+    }
+}
+
+fn get_config_dir(scope: Scope) -> Result<PathBuf, LaneError> {
+    match scope {
+        Scope::Project => get_project_dir(),
+        _ => home_dir().ok_or(LaneError::NoHomeDir()),
+    }
+}
+
+// "yarn@4.1.0" set by corepack in `packageManager` of package.json
+fn format_of_package_manager(package_manager: &str) -> Option<Format> {
+    let version = package_manager.strip_prefix("yarn@")?;
+    match version.split('.').next()?.parse::<u32>().ok()? {
+        0 | 1 => Some(Format::Classic),
+        _ => Some(Format::Berry),
+    }
+}
+
+// lockfiles of berry are YAML with a `__metadata` entry
+fn format_of_lockfile(content: &str) -> Format {
+    match content.lines().any(|line| line.starts_with("__metadata:")) {
+        true => Format::Berry,
+        false => Format::Classic,
+    }
+}
+
+// format of the yarn version used by the current project, or classic, which is the one installed
+// globally usually
+fn detect_format() -> Format {
+    let dir = match get_project_dir() {
+        Ok(dir) => dir,
+        Err(_) => return Format::Classic,
+    };
+    let package_json = read_file(&dir.join(PACKAGE_JSON)).unwrap_or_default();
+    let package: serde_json::Value = serde_json::from_str(&package_json).unwrap_or_default();
+    if let Some(format) = package["packageManager"].as_str().and_then(format_of_package_manager) {
+        return format;
+    }
+    match read_file(&dir.join(LOCKFILE)) {
+        Ok(content) => format_of_lockfile(&content),
+        Err(_) => Format::Classic,
+    }
+}
+
+// berry allows renaming its config file by env
+fn get_config_files(scope: Scope) -> Result<Vec<(PathBuf, Format)>, LaneError> {
+    let dir = get_config_dir(scope)?;
+    let berry_config = env::var("YARN_RC_FILENAME").ok().filter(|name| !name.is_empty());
+    Ok(vec![
+        (dir.join(CLASSIC_CONFIG), Format::Classic),
+        (dir.join(berry_config.as_deref().unwrap_or(BERRY_CONFIG)), Format::Berry),
+    ])
+}
+
+fn read_config(path: &Path, format: Format) -> Result<Option<Yarnrc>, LaneError> {
+    match path.exists() {
+        true => match read_file(path) {
+            Ok(content) => Ok(Some(Yarnrc::parse(format, &content))),
+            Err(_) => Err(make_invalid_file_error(path)),
+        },
+        false => Ok(None),
+    }
+}
+
+// existing config files of the scope
+fn read_configs(scope: Scope) -> Result<Vec<(PathBuf, Yarnrc)>, LaneError> {
+    let mut configs = vec![];
+    for (path, format) in get_config_files(scope)? {
+        if let Some(config) = read_config(&path, format)? {
+            configs.push((path, config));
+        }
+    }
+    Ok(configs)
+}
+
+// berry reads env vars like YARN_NPM_REGISTRY_SERVER for npmRegistryServer
+fn warn_on_env_override(key: &str) {
+    let mut name = String::from("YARN_");
+    for c in key.chars() {
+        if c.is_ascii_uppercase() {
+            name.push('_');
+        }
+        name.push(c.to_ascii_uppercase());
+    }
+    if let Some(value) = env::var(&name).ok().filter(|v| !v.is_empty()) {
+        warn!("{} is overridden by env {} as {}", key, name, value);
+    }
+}
+
+// edit existing config files of the scope, or the one of the yarn version in use when none exists.
+// `edit` returns false if nothing is changed.
+fn update_configs(
+    scope: Scope,
+    create: bool,
+    edit: impl Fn(&mut Yarnrc, &Keys) -> bool,
+) -> Result<(), LaneError> {
+    let mut configs = read_configs(scope)?;
+    if configs.is_empty() && create {
+        let format = detect_format();
+        for (path, _) in get_config_files(scope)?.into_iter().filter(|(_, f)| *f == format) {
+            configs.push((path, Yarnrc::parse(format, "")));
+        }
+    }
+    let mut result = Err(LaneError::NothingToDo());
+    for (path, mut config) in configs {
+        let keys = keys_of(config.format());
+        if edit(&mut config, keys) {
+            write_config_file(&path, &config.to_string())?;
+            result = Ok(());
+        }
+    }
+    result
+}
+
+pub struct Yarn;
+
+impl App for Yarn {
+    fn name(&self) -> &'static str {
+        "yarn"
+    }
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::Proxy, Capability::Mirror]
+    }
+
+    fn scopes(&self) -> &'static [Scope] {
+        &[Scope::Project, Scope::User]
+    }
+
+    fn config_files(&self, scope: Scope) -> Vec<PathBuf> {
+        get_config_files(scope).unwrap_or_default().into_iter().map(|(path, _)| path).collect()
+    }
+
+    // keys other than the http proxy are shown as subsections to tell them apart
+    fn get_proxies(&self, scope: Scope) -> Result<Vec<Proxy>, LaneError> {
+        let mut proxies = vec![];
+        for (path, config) in read_configs(scope)? {
+            let keys = keys_of(config.format());
+            for key in [keys.proxy, keys.https_proxy] {
+                if let Some(value) = config.get(key) {
+                    let mut proxy = Proxy::new(scope, Some(&path), key, value);
+                    proxy.subsection = (key != keys.proxy).then(|| key.to_string());
+                    proxies.push(proxy);
+                }
+            }
+        }
+        Ok(proxies)
+    }
+
+    fn set_proxy(&self, scope: Scope, proxy: &str) -> Result<(), LaneError> {
+        validate_proxy_url(proxy)?;
+        warn_on_env_override(BERRY_KEYS.proxy);
+        warn_on_env_override(BERRY_KEYS.https_proxy);
+        update_configs(scope, true, |config, keys| {
+            config.set(keys.proxy, proxy);
+            config.set(keys.https_proxy, proxy);
+            true
+        })
+    }
+
+    fn unset_proxy(&self, scope: Scope) -> Result<(), LaneError> {
+        update_configs(scope, false, |config, keys| {
+            config.unset(keys.proxy) | config.unset(keys.https_proxy)
+        })
+    }
+
+    // yarn uses the same registries as npm
+    fn builtin_mirrors(&self) -> Vec<MirrorEntry> {
+        mirror_entries::<npm::Mirrors>()
+    }
+
+    fn get_mirror(&self, scope: Scope) -> Result<Option<Mirror>, LaneError> {
+        for (path, config) in read_configs(scope)? {
+            let key = keys_of(config.format()).registry;
+            let Some(registry) = config.get(key) else {
+                continue;
+            };
+            let name = self.find_mirror_by_url(registry)?.map(|mirror| mirror.name);
+            let mut mirror = Mirror::new(scope, Some(&path), key, name, registry);
+            let auth_key = keys_of(config.format()).auth_token;
+            mirror.auth = auth_key.and_then(|key| config.get(key)).map(mask_secret);
+            return Ok(Some(mirror));
+        }
+        Ok(None)
+    }
+
+    fn set_mirror(&self, scope: Scope, mirror: &MirrorEntry) -> Result<(), LaneError> {
+        warn_on_env_override(BERRY_KEYS.registry);
+        update_configs(scope, true, |config, keys| {
+            config.set(keys.registry, &mirror.url);
+            match (keys.auth_token, &mirror.auth) {
+                (Some(key), Some(token)) => config.set(key, token),
+                // a token not bound to the registry must not be sent to another one
+                (Some(key), None) => {
+                    config.unset(key);
+                }
+                (None, Some(_)) => warn!(
+                    "Token of {} is not set in .yarnrc, as yarn classic reads tokens from .npmrc \
+                     only, try `lane set-mirror npm`",
+                    mirror.url
+                ),
+                (None, None) => {}
+            }
+            true
+        })
+    }
+
+    fn unset_mirror(&self, scope: Scope) -> Result<(), LaneError> {
+        update_configs(scope, false, |config, keys| {
+            let unset = config.unset(keys.registry);
+            if let (true, Some(auth_key)) = (unset, keys.auth_token) {
+                config.unset(auth_key);
+            }
+            unset
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_format_of_project() {
+        assert_eq!(format_of_package_manager("yarn@1.22.19"), Some(Format::Classic));
+        assert_eq!(format_of_package_manager("yarn@4.1.0+sha224.953c8233"), Some(Format::Berry));
+        assert_eq!(format_of_package_manager("pnpm@8.6.0"), None);
+        let classic = "# THIS IS AN AUTOGENERATED FILE.\n# yarn lockfile v1\n\nleft-pad@^1.3.0:\n";
+        assert_eq!(format_of_lockfile(classic), Format::Classic);
+        let berry = "__metadata:\n  version: 8\n  cacheKey: 10c0\n\n\"left-pad@npm:^1.3.0\":\n";
+        assert_eq!(format_of_lockfile(berry), Format::Berry);
+    }
+}
@@ -0,0 +1,234 @@
+// Format preserving editors of yarn config files: `.yarnrc` of yarn classic
+// (https://classic.yarnpkg.com/en/docs/yarnrc) and `.yarnrc.yml` of yarn berry
+// (https://yarnpkg.com/configuration/yarnrc).
+//
+// Only top level keys are managed, other lines including comments are kept as is.
+
+use std::fmt::Display;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// `key "value"` lines of yarn 1
+    Classic,
+    /// YAML of yarn 2 and later
+    Berry,
+}
+
+#[derive(Clone, Debug)]
+struct Line {
+    text: String,
+    /// Top level key starting the line
+    key: Option<String>,
+    /// Value of the key, `None` for values not on the same line, like a mapping of YAML
+    value: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Yarnrc {
+    format: Format,
+    lines: Vec<Line>,
+}
+
+// strip quotes of a double quoted (JSON compatible) or single quoted value
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        return serde_json::from_str(value).unwrap_or_else(|_| value[1..value.len() - 1].into());
+    }
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        return value[1..value.len() - 1].replace("''", "'");
+    }
+    value.to_string()
+}
+
+// split the leading key, which may be quoted, from the rest of the line
+fn split_key(line: &str, separator: char) -> Option<(String, &str)> {
+    if let Some(rest) = line.strip_prefix('"') {
+        let end = rest.find('"')?;
+        return Some((rest[..end].to_string(), &rest[end + 1..]));
+    }
+    let end = line.find(separator).unwrap_or(line.len());
+    Some((line[..end].trim_end().to_string(), &line[end..]))
+}
+
+// "key value", where both could be quoted
+fn parse_classic(line: &str) -> Option<(String, Option<String>)> {
+    if line.starts_with(char::is_whitespace) || line.trim().is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (key, rest) = split_key(line.trim_end(), ' ')?;
+    Some((key, Some(unquote(rest))))
+}
+
+// length of the quoted string at the start of the value, or the whole value if not closed
+fn quoted_len(value: &str, quote: char) -> usize {
+    let mut chars = value.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if quote == '"' => {
+                chars.next();
+            }
+            // '' is an escaped quote in a single quoted string
+            '\'' if quote == '\'' && value[i + 1..].starts_with('\'') => {
+                chars.next();
+            }
+            c if c == quote => return i + 1,
+            _ => {}
+        }
+    }
+    value.len()
+}
+
+// "key: value" of a top level YAML mapping, a value not quoted ends at " #"
+fn parse_berry(line: &str) -> Option<(String, Option<String>)> {
+    if line.starts_with(char::is_whitespace) || line.trim().is_empty() {
+        return None;
+    }
+    if line.starts_with(['#', '-']) || line.starts_with("---") {
+        return None;
+    }
+    let (key, rest) = split_key(line.trim_end(), ':')?;
+    let value = rest.strip_prefix(':')?.trim();
+    let value = match value.chars().next() {
+        Some(quote @ ('"' | '\'')) => &value[..quoted_len(value, quote)],
+        _ => value.split(" #").next().unwrap_or_default().trim(),
+    };
+    match value.is_empty() {
+        true => Some((key, None)),
+        false => Some((key, Some(unquote(value)))),
+    }
+}
+
+impl Yarnrc {
+    pub fn parse(format: Format, content: &str) -> Self {
+        let parse_line = match format {
+            Format::Classic => parse_classic,
+            Format::Berry => parse_berry,
+        };
+        let lines = content
+            .split('\n')
+            .map(|text| {
+                let (key, value) = parse_line(text).unzip();
+                Line { text: text.to_string(), key, value: value.flatten() }
+            })
+            .collect();
+        Yarnrc { format, lines }
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    // indexes of lines starting the key
+    fn find(&self, key: &str) -> Vec<usize> {
+        let matches = |line: &Line| line.key.as_deref() == Some(key);
+        (0..self.lines.len()).filter(|i| matches(&self.lines[*i])).collect()
+    }
+
+    // number of lines of the value starting at the index, including nested lines of YAML
+    fn extent(&self, index: usize) -> usize {
+        let nested =
+            |line: &Line| self.format == Format::Berry && line.text.starts_with([' ', '\t', '-']);
+        1 + self.lines[index + 1..].iter().take_while(|line| nested(line)).count()
+    }
+
+    /// Value of the key, the last one wins
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let index = *self.find(key).last()?;
+        self.lines[index].value.as_deref()
+    }
+
+    /// Set the value in place of the existing one, or add it at the end
+    pub fn set(&mut self, key: &str, value: &str) {
+        let quoted = serde_json::to_string(value).unwrap_or_else(|_| value.to_string());
+        let text = match self.format {
+            Format::Classic => format!("{} {}", key, quoted),
+            Format::Berry => format!("{}: {}", key, quoted),
+        };
+        let line = Line { text, key: Some(key.to_string()), value: Some(value.to_string()) };
+        let indexes = self.find(key);
+        if let Some((last, others)) = indexes.split_last() {
+            let extent = self.extent(*last);
+            self.lines.splice(*last..*last + extent, [line]);
+            for index in others.iter().rev() {
+                let extent = self.extent(*index);
+                self.lines.drain(*index..*index + extent);
+            }
+            return;
+        }
+        // before the empty last line of a file ending with newline
+        let mut index = self.lines.len();
+        while index > 0 && self.lines[index - 1].text.trim().is_empty() {
+            index -= 1;
+        }
+        self.lines.insert(index, line);
+        if self.lines.last().is_some_and(|line| !line.text.is_empty()) {
+            self.lines.push(Line { text: String::new(), key: None, value: None });
+        }
+    }
+
+    /// Remove the key, returns whether it existed
+    pub fn unset(&mut self, key: &str) -> bool {
+        let indexes = self.find(key);
+        for index in indexes.iter().rev() {
+            let extent = self.extent(*index);
+            self.lines.drain(*index..*index + extent);
+        }
+        !indexes.is_empty()
+    }
+}
+
+impl Display for Yarnrc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lines: Vec<&str> = self.lines.iter().map(|line| line.text.as_str()).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YARNRC: &str = "# yarn lockfile v1\nregistry \"https://registry.yarnpkg.com\"\n\
+                          \"--install.frozen-lockfile\" true\nlastUpdateCheck 1700000000000\n";
+
+    const YARNRC_YML: &str = "# berry\nnodeLinker: node-modules\n\
+                              npmRegistryServer: 'https://npm.corp' # internal\n\
+                              packageExtensions:\n  \"a@*\":\n    dependencies:\n      b: \"*\"\n\
+                              httpProxy: http://127.0.0.1:8080\n";
+
+    #[test]
+    fn parse_and_set_classic() {
+        let mut config = Yarnrc::parse(Format::Classic, YARNRC);
+        assert_eq!(config.to_string(), YARNRC);
+        assert_eq!(config.get("registry"), Some("https://registry.yarnpkg.com"));
+        assert_eq!(config.get("--install.frozen-lockfile"), Some("true"));
+        config.set("registry", "https://registry.npmmirror.com/");
+        config.set("proxy", "http://127.0.0.1:8080");
+        let expected = YARNRC
+            .replace("https://registry.yarnpkg.com", "https://registry.npmmirror.com/")
+            + "proxy \"http://127.0.0.1:8080\"\n";
+        assert_eq!(config.to_string(), expected);
+        assert!(config.unset("proxy"));
+        assert!(!config.unset("proxy"));
+    }
+
+    #[test]
+    fn parse_and_set_berry() {
+        let mut config = Yarnrc::parse(Format::Berry, YARNRC_YML);
+        assert_eq!(config.to_string(), YARNRC_YML);
+        assert_eq!(config.get("npmRegistryServer"), Some("https://npm.corp"));
+        assert_eq!(config.get("httpProxy"), Some("http://127.0.0.1:8080"));
+        assert_eq!(config.get("packageExtensions"), None);
+        config.set("npmRegistryServer", "https://registry.npmmirror.com/");
+        assert!(config.unset("packageExtensions"));
+        assert!(config.unset("httpProxy"));
+        let expected =
+            "# berry\nnodeLinker: node-modules\nnpmRegistryServer: \"https://registry.npmmirror.com/\"\n";
+        assert_eq!(config.to_string(), expected);
+
+        let mut config = Yarnrc::parse(Format::Berry, "");
+        config.set("httpsProxy", "http://127.0.0.1:8080");
+        assert_eq!(config.to_string(), "httpsProxy: \"http://127.0.0.1:8080\"\n");
+    }
+}